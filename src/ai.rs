@@ -1,54 +1,524 @@
 use neat::ga::GA;
 use neat::phenotype::RunType;
-use neat::phenotype::NeuralNet;
+use neat::phenotype::{NeuralNet, NeuronType};
+use super::netdraw;
+use super::novelty::{self, NoveltyArchive};
+use super::persist::{invalid_data, Checkpoint, SavedGA, SavedNet, CHECKPOINT_FILE_VERSION};
+use super::curriculum::{Curriculum, CurriculumStage};
+use super::fitness::{Aggregation, EpisodeEnd, EpisodeStats, FitnessFunction, WeightedBlend};
+use super::imitation::{self, ImitationModel};
+use super::mutation::{self, WeightPerturbation};
+use super::random::Random;
+use super::simulation::{GameEvent, WorldConfig, MAX_CAR_SPEED};
+use super::stats::{GenerationStats, StatsWriter};
+use super::sensors::SensorConfig;
+use super::stagnation::{self, EpisodeMonitor, PopulationStagnation, StagnationAction, StagnationDetector};
+use serde_derive::{Deserialize, Serialize};
+use super::training::{self, EpisodeLimits};
+use std::io;
+use std::path::{Path, PathBuf};
 
-const POP_SIZE:i32 = 150;
+//默认的种群数量
+pub const POP_SIZE:i32 = 150;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Turn{
     Left,
-    Right
+    Right,
+    //减速直到停下
+    Idle,
+    //以最大加速度接近目标速度(像素/帧)
+    Target(f64),
+}
+
+//汽车的动作
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Action{
+    pub turn: Turn,
+    pub fire: bool,
+}
+
+impl Action{
+    pub fn new(turn: Turn, fire: bool) -> Action{
+        Action{ turn, fire }
+    }
+}
+
+//网络输出的含义
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionMode{
+    //2个输出：左转/右转、开火
+    #[default]
+    Binary,
+    //3个输出：左转、右转(都不大于0.5或都大于0.5时不动)、开火
+    Idle,
+    //2个输出：目标速度(0~1对应最大倒车速度~最大前进速度)、开火
+    Analog,
+}
+
+pub const ACTION_MODE_NAMES: &[&str] = &["binary", "idle", "analog"];
+
+impl ActionMode{
+    pub fn from_name(name: &str) -> Option<ActionMode>{
+        match name{
+            "binary" => Some(ActionMode::Binary),
+            "idle" => Some(ActionMode::Idle),
+            "analog" => Some(ActionMode::Analog),
+            _ => None,
+        }
+    }
+
+    pub fn num_outputs(&self) -> usize{
+        self.output_names().len()
+    }
+
+    //每个输出的名字，用于显示
+    pub fn output_names(&self) -> &'static [&'static str]{
+        match self{
+            ActionMode::Binary => &["左/右", "开火"],
+            ActionMode::Idle => &["左", "右", "开火"],
+            ActionMode::Analog => &["速度", "开火"],
+        }
+    }
+}
+
+//把网络输出转换成汽车的动作
+pub fn decide(output: &[f64], mode: ActionMode) -> Action{
+    match mode{
+        ActionMode::Binary => {
+            let fire = output[1] > 0.5;
+            if output[0] > 0.5 {
+                Action::new(Turn::Left, fire)
+            }else{
+                Action::new(Turn::Right, fire)
+            }
+        }
+        ActionMode::Idle => {
+            let turn = match (output[0] > 0.5, output[1] > 0.5){
+                (true, false) => Turn::Left,
+                (false, true) => Turn::Right,
+                _ => Turn::Idle,
+            };
+            Action::new(turn, output[2] > 0.5)
+        }
+        ActionMode::Analog => {
+            let speed = (output[0] * 2.0 - 1.0) * MAX_CAR_SPEED;
+            Action::new(Turn::Target(speed), output[1] > 0.5)
+        }
+    }
+}
+
+//一帧中网络各层的激活值
+#[derive(Clone, Debug, Default)]
+pub struct Activations{
+    pub inputs: Vec<f64>,
+    pub hidden: Vec<f64>,
+    pub outputs: Vec<f64>,
+}
+
+//快速模式下分批评估的一代：网络的副本、各局的种子和已经评估完的结果
+struct PendingGeneration{
+    nets: Vec<NeuralNet>,
+    seeds: Vec<u64>,
+    results: Vec<Vec<EpisodeStats>>,
 }
 
 pub struct CarBrain{
     ga: GA,
+    sensors: SensorConfig,
     generation: i32,
     current_brain: usize,
-    frame_count: f64,
+    //当前网络这一局的统计
+    episode: EpisodeStats,
+    fitness: Box<dyn FitnessFunction>,
+    //每个网络玩的局数，以及各局得分的合并方式
+    episodes: usize,
+    aggregation: Aggregation,
+    //当前网络已经玩完的各局得分
+    episode_scores: Vec<f64>,
     max_score: f64,
     use_best: bool,
     best_brain: Option<NeuralNet>,
+    save_path: Option<PathBuf>,
+    net_dir: Option<PathBuf>,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_interval: i32,
+    seed: u64,
+    stats_writer: Option<StatsWriter>,
+    last_stats: Option<GenerationStats>,
+    //不使用课程训练时的游戏世界难度，以及一局的结束条件
+    world: WorldConfig,
+    limits: EpisodeLimits,
+    curriculum: Option<Curriculum>,
+    curriculum_stage: usize,
+    //新颖性搜索的存档和得分权重，None时只按得分选择
+    novelty: Option<NoveltyArchive>,
+    novelty_fitness_weight: f64,
+    //这一代每个网络的行为描述，以及当前网络已经玩完的各局行为描述
+    behaviours: Vec<Vec<f64>>,
+    episode_behaviours: Vec<Vec<f64>>,
+    activations: Activations,
+    //当前这一局的停滞检测和结束原因，以及这一代所有局的结束原因
+    monitor: EpisodeMonitor,
+    episode_end: Option<EpisodeEnd>,
+    episode_ends: Vec<EpisodeEnd>,
+    //种群停滞的检测和处理
+    stagnation: PopulationStagnation,
+    stagnation_detector: StagnationDetector,
+    //每一代繁殖后在neat的变异之外额外扰动连接权重
+    weight_perturbation: WeightPerturbation,
+    pending: Option<PendingGeneration>,
+}
+
+impl Default for CarBrain{
+    fn default() -> CarBrain{
+        CarBrain::new()
+    }
 }
 
 impl CarBrain{
     pub fn new() -> CarBrain{
-        let mut ga = GA::new(POP_SIZE, 3, 2);
+        CarBrain::with_sensors(SensorConfig::default())
+    }
+
+    //网络的输入数量由传感器配置决定
+    pub fn with_sensors(sensors: SensorConfig) -> CarBrain{
+        CarBrain::with_population(sensors, POP_SIZE)
+    }
+
+    //指定种群数量
+    pub fn with_population(sensors: SensorConfig, pop_size: i32) -> CarBrain{
+        let mut ga = GA::new(pop_size.max(1), sensors.num_inputs() as i32, sensors.num_outputs() as i32);
         ga.create_phenotypes();
+        CarBrain::from_ga(ga, sensors)
+    }
+
+    //用已有的种群创建，其它状态都是初始值
+    fn from_ga(ga: GA, sensors: SensorConfig) -> CarBrain{
         CarBrain{
             ga,
+            sensors,
             generation: 0,
             current_brain: 0,
-            frame_count: 0.0,
+            episode: EpisodeStats::default(),
+            fitness: Box::new(WeightedBlend::default()),
+            episodes: 1,
+            aggregation: Aggregation::default(),
+            episode_scores: vec![],
             max_score: 0.0,
             use_best: false,
             best_brain: None,
+            save_path: None,
+            net_dir: None,
+            checkpoint_path: None,
+            checkpoint_interval: 10,
+            seed: 0,
+            stats_writer: None,
+            last_stats: None,
+            world: WorldConfig::default(),
+            limits: EpisodeLimits::default(),
+            curriculum: None,
+            curriculum_stage: 0,
+            novelty: None,
+            novelty_fitness_weight: 0.0,
+            behaviours: vec![],
+            episode_behaviours: vec![],
+            activations: Activations::default(),
+            monitor: EpisodeMonitor::default(),
+            episode_end: None,
+            episode_ends: vec![],
+            stagnation: PopulationStagnation::default(),
+            stagnation_detector: StagnationDetector::default(),
+            weight_perturbation: WeightPerturbation::default(),
+            pending: None,
         }
     }
 
+    //从检查点恢复训练，恢复后需要用和原来相同的设置调用configure
+    //当前网络正在玩的这一局没有保存，从这一局的开头重新开始
+    pub fn resume<P: AsRef<Path>>(path: P) -> io::Result<CarBrain>{
+        let checkpoint = Checkpoint::load(path)?;
+        let mut ga = checkpoint.ga.to_ga(checkpoint.sensors.num_inputs() as i32, checkpoint.sensors.num_outputs() as i32)?;
+        if checkpoint.fitness_scores.len() != ga.pop_size() as usize
+            || checkpoint.current_brain >= ga.pop_size() as usize{
+            return Err(invalid_data("检查点中的得分数量不正确"));
+        }
+        *ga.fitness_scores() = checkpoint.fitness_scores;
+        let best_brain = match checkpoint.best_brain{
+            Some(net) => Some(net.to_net()?),
+            None => None,
+        };
+        let mut brain = CarBrain::from_ga(ga, checkpoint.sensors);
+        brain.generation = checkpoint.generation;
+        brain.current_brain = checkpoint.current_brain;
+        brain.max_score = checkpoint.max_score;
+        brain.best_brain = best_brain;
+        brain.curriculum_stage = checkpoint.curriculum_stage;
+        brain.episode_scores = checkpoint.episode_scores;
+        brain.episode_behaviours = checkpoint.episode_behaviours;
+        brain.behaviours = checkpoint.behaviours;
+        brain.episode_ends = checkpoint.episode_ends;
+        brain.novelty = checkpoint.novelty;
+        brain.stagnation_detector = checkpoint.stagnation;
+        Ok(brain)
+    }
+
+    //保存整个种群到检查点文件
+    pub fn save_checkpoint<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()>{
+        Checkpoint{
+            version: CHECKPOINT_FILE_VERSION,
+            sensors: self.sensors.clone(),
+            generation: self.generation,
+            current_brain: self.current_brain,
+            max_score: self.max_score,
+            fitness_scores: self.ga.fitness_scores().clone(),
+            best_brain: self.best_brain.as_ref().map(|net| SavedNet::from_net(net, &self.sensors)),
+            ga: SavedGA::from_ga(&self.ga),
+            curriculum_stage: self.curriculum_stage,
+            episode_scores: self.episode_scores.clone(),
+            episode_behaviours: self.episode_behaviours.clone(),
+            behaviours: self.behaviours.clone(),
+            episode_ends: self.episode_ends.clone(),
+            novelty: self.novelty.clone(),
+            stagnation: self.stagnation_detector.clone(),
+        }.save(path)
+    }
+
+    //每隔interval代自动保存一次检查点
+    pub fn set_checkpoint(&mut self, path: Option<PathBuf>, interval: i32){
+        self.checkpoint_path = path;
+        self.checkpoint_interval = interval.max(1);
+    }
+
     //下一代
-    fn epoch(&mut self) -> Option<String>{
+    fn epoch(&mut self){
+        //逐帧训练完成了这一代时丢弃还没有评估完的批次
+        self.pending = None;
+        self.record_generation_stats();
+        self.update_curriculum();
+        //新颖性搜索会替换选择使用的得分，最好的网络要在这之前按原来的得分选出
+        let fittest = self.fittest_phenotype();
+        self.apply_novelty();
         self.ga.epoch();
         self.generation += 1;
         self.current_brain = 0;
-        //绘制最好的网络
-        let brains: Vec<usize> = self.ga.get_best_phenotypes_from_last_generation();
-        if brains.len() > 0 {
-            self.best_brain = Some(self.ga.get_phenotype(brains[0]).clone());
-            let net_img = self.ga.get_phenotype(brains[0]).draw_net(120, 100, 10);
-            Some(net_img)
-        }else{
-            None
+        if self.weight_perturbation.rate > 0.0{
+            let mut rng = Random::new(self.seed.rotate_left(32) ^ self.generation as u64);
+            mutation::perturb_weights(&mut self.ga, self.weight_perturbation.rate, self.weight_perturbation.power, &mut rng);
+        }
+        self.update_best_brain(fittest);
+        self.handle_stagnation();
+        //自动保存检查点
+        if self.generation % self.checkpoint_interval == 0{
+            if let Some(path) = self.checkpoint_path.clone(){
+                match self.save_checkpoint(&path){
+                    Ok(()) => log!("第{}代检查点已保存:{:?}", self.generation, path),
+                    Err(err) => log!("保存检查点失败:{:?} {:?}", path, err),
+                }
+            }
+        }
+    }
+
+    //统计这一代的得分和基因组复杂度
+    fn record_generation_stats(&mut self){
+        let scores = self.ga.fitness_scores().clone();
+        let genome_sizes: Vec<(usize, usize)> = self.ga.genomes.iter()
+            .map(|genome| (genome.neurons.len(), genome.links.iter().filter(|link| link.enabled).count()))
+            .collect();
+        let mut stats = GenerationStats::new(self.generation, &scores, self.ga.species.len(), &genome_sizes);
+        let ends = std::mem::take(&mut self.episode_ends);
+        stats.timeouts = ends.iter().filter(|&&end| end == EpisodeEnd::Timeout).count();
+        stats.stagnations = ends.iter().filter(|&&end| end == EpisodeEnd::Stagnation).count();
+        if let Some(writer) = self.stats_writer.as_mut(){
+            if let Err(err) = writer.write(&stats){
+                log!("写入训练统计失败:{:?}", err);
+            }
+        }
+        self.last_stats = Some(stats);
+    }
+
+    //最高得分多代没有提高时重新生成种群或加大变异
+    fn handle_stagnation(&mut self){
+        let best = match self.last_stats.as_ref(){
+            Some(stats) => stats.best,
+            None => return,
+        };
+        if !self.stagnation_detector.update(&self.stagnation, best){
+            return;
+        }
+        match self.stagnation.action{
+            StagnationAction::Reseed => {
+                let mut ga = GA::new(self.ga.pop_size(), self.sensors.num_inputs() as i32, self.sensors.num_outputs() as i32);
+                ga.create_phenotypes();
+                self.ga = ga;
+                log!("种群{}代没有进步，重新生成种群(最好的网络不进入新的种群)", self.stagnation.generations);
+            }
+            StagnationAction::Boost => {
+                let mut rng = Random::new(!self.seed ^ self.generation as u64);
+                stagnation::boost_mutation(&mut self.ga, &self.stagnation, &mut rng);
+                log!("种群{}代没有进步，加大变异", self.stagnation.generations);
+            }
+        }
+    }
+
+    //neat的变异之外额外扰动连接权重的概率和幅度
+    pub fn set_weight_perturbation(&mut self, perturbation: WeightPerturbation){
+        self.weight_perturbation = perturbation;
+    }
+
+    //种群停滞的判断条件和处理方式
+    pub fn set_stagnation(&mut self, stagnation: PopulationStagnation){
+        //从检查点恢复的计数只在设置改变时重新开始
+        if stagnation != self.stagnation{
+            self.stagnation_detector.reset();
+        }
+        self.stagnation = stagnation;
+    }
+
+    //新颖性搜索：用新颖性(和得分的混合)代替得分进行选择，训练统计仍然使用原来的得分
+    fn apply_novelty(&mut self){
+        let behaviours = std::mem::take(&mut self.behaviours);
+        let archive = match self.novelty.as_mut(){
+            Some(archive) => archive,
+            None => return,
+        };
+        //从检查点恢复的这一代没有完整的行为记录
+        if behaviours.len() != self.ga.pop_size() as usize || behaviours.iter().any(|b| b.is_empty()){
+            return;
+        }
+        let novelty = archive.evaluate(&behaviours);
+        let scores = novelty::blend(&novelty, self.ga.fitness_scores(), self.novelty_fitness_weight);
+        *self.ga.fitness_scores() = scores;
+    }
+
+    //使用新颖性搜索，fitness_weight为得分所占的比例(0~1)，None表示只按得分选择
+    pub fn set_novelty(&mut self, fitness_weight: Option<f64>){
+        match fitness_weight{
+            Some(weight) => {
+                //保留从检查点恢复的存档
+                if self.novelty.is_none(){
+                    self.novelty = Some(NoveltyArchive::default());
+                }
+                self.novelty_fitness_weight = weight.clamp(0.0, 1.0);
+            }
+            None => self.novelty = None,
+        }
+    }
+
+    //新颖性存档中的行为数量
+    pub fn novelty_archive_size(&self) -> Option<usize>{
+        self.novelty.as_ref().map(|archive| archive.archive_size())
+    }
+
+    //记录网络的行为描述
+    fn set_behaviour(&mut self, index: usize, behaviour: Vec<f64>){
+        if self.behaviours.len() != self.ga.pop_size() as usize{
+            self.behaviours = vec![vec![]; self.ga.pop_size() as usize];
         }
+        self.behaviours[index] = behaviour;
+    }
+
+    //上一代的平均得分达到门槛后进入课程的下一阶段
+    fn update_curriculum(&mut self){
+        let mean = match self.last_stats.as_ref(){
+            Some(stats) => stats.mean,
+            None => return,
+        };
+        if let Some(curriculum) = self.curriculum.as_ref(){
+            let stage = curriculum.next_stage(self.curriculum_stage, mean);
+            if stage != self.curriculum_stage{
+                self.curriculum_stage = stage;
+                self.stagnation_detector.reset();
+                log!("第{}代进入课程阶段:{}", self.generation + 1, curriculum.stages[stage].name);
+            }
+        }
+    }
+
+    //使用课程训练，None表示始终使用默认难度
+    pub fn set_curriculum(&mut self, curriculum: Option<Curriculum>){
+        if let Some(curriculum) = curriculum.as_ref(){
+            //从检查点恢复时保留阶段
+            self.curriculum_stage = self.curriculum_stage.min(curriculum.stages.len().saturating_sub(1));
+        }
+        self.curriculum = curriculum;
+    }
+
+    //当前课程阶段的序号和设置
+    pub fn curriculum_stage(&self) -> Option<(usize, &CurriculumStage)>{
+        self.curriculum.as_ref()
+            .and_then(|curriculum| curriculum.stages.get(self.curriculum_stage))
+            .map(|stage| (self.curriculum_stage, stage))
+    }
+
+    //训练使用的游戏世界难度
+    pub fn world_config(&self) -> WorldConfig{
+        match self.curriculum_stage(){
+            Some((_, stage)) => stage.world.clone(),
+            None => self.world.clone(),
+        }
+    }
+
+    //不使用课程训练时的游戏世界难度
+    pub fn set_world_config(&mut self, world: WorldConfig){
+        self.world = world;
+    }
+
+    pub fn set_limits(&mut self, limits: EpisodeLimits){
+        self.limits = limits;
+    }
+
+    //当前网络这一局是否因为超时或停滞需要结束
+    pub fn episode_end(&self) -> Option<EpisodeEnd>{
+        self.episode_end
+    }
+
+    //上一代的训练统计
+    pub fn last_stats(&self) -> Option<&GenerationStats>{
+        self.last_stats.as_ref()
+    }
+
+    //把每一代的训练统计写入文件(.csv为CSV格式，其它为JSON lines)
+    pub fn set_stats_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()>{
+        self.stats_writer = Some(StatsWriter::open(path)?);
+        Ok(())
+    }
+
+    //这一代得分最高的网络
+    fn fittest_phenotype(&mut self) -> Option<NeuralNet>{
+        let scores = self.ga.fitness_scores();
+        let best = (0..scores.len()).max_by(|&a, &b| scores[a].partial_cmp(&scores[b]).unwrap_or(std::cmp::Ordering::Equal))?;
+        Some(self.ga.get_phenotype(best).clone())
+    }
+
+    //记录最好的网络
+    fn update_best_brain(&mut self, fittest: Option<NeuralNet>){
+        if let Some(net) = fittest {
+            //把网络结构图保存到目录
+            if let Some(dir) = self.net_dir.as_ref(){
+                let saved = SavedNet::from_net(&net, &self.sensors);
+                if let Err(err) = netdraw::save_generation(dir, self.generation, &saved){
+                    log!("保存网络结构图失败:{:?} {:?}", dir, err);
+                }
+            }
+            self.best_brain = Some(net);
+            //自动保存最好的网络
+            if let Some(path) = self.save_path.clone(){
+                if let Err(err) = self.save_best(&path){
+                    log!("保存网络失败:{:?} {:?}", path, err);
+                }
+            }
+        }
+    }
+
+    //最好的网络(上一代的最好网络或加载的网络)
+    pub fn best_brain(&self) -> Option<&NeuralNet>{
+        self.best_brain.as_ref()
+    }
+
+    //每一代结束后把最好的网络结构图(SVG和DOT)保存到指定目录
+    pub fn set_net_dir(&mut self, dir: Option<PathBuf>){
+        self.net_dir = dir;
     }
 
     pub fn current_brain(&self) -> usize{
@@ -59,47 +529,241 @@ impl CarBrain{
         self.generation
     }
 
+    pub fn sensors(&self) -> &SensorConfig{
+        &self.sensors
+    }
+
     pub fn use_best(&mut self, use_best:bool){
         self.use_best = use_best;
     }
 
+    //每一代结束后把最好的网络保存到指定文件
+    pub fn set_save_path(&mut self, path: Option<PathBuf>){
+        self.save_path = path;
+    }
+
+    //保存最好的网络到文件(JSON格式，见persist.rs)
+    pub fn save_best<P: AsRef<Path>>(&self, path: P) -> io::Result<()>{
+        match self.best_brain.as_ref(){
+            Some(brain) => SavedNet::from_net(brain, &self.sensors).save(path),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "还没有最好的网络")),
+        }
+    }
+
+    //从文件加载最好的网络
+    pub fn load_best<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()>{
+        let net = SavedNet::load(path)?;
+        net.check_sensors(&self.sensors)?;
+        let net = net.to_net()?;
+        self.best_brain = Some(net);
+        Ok(())
+    }
+
+    //用模仿人类驾驶拟合的权重初始化种群，每个权重加上随机扰动
+    pub fn imitate(&mut self, model: &ImitationModel, noise: f64) -> io::Result<()>{
+        if model.sensors != self.sensors {
+            return Err(invalid_data("模仿网络使用了不同的传感器配置"));
+        }
+        let mut rng = Random::new(!self.seed);
+        imitation::seed_population(&mut self.ga, model, noise, &mut rng);
+        Ok(())
+    }
+
     pub fn max_score(&self) -> i32{
         self.max_score as i32
     }
 
-    //汽车被子弹击中以后，进入下一个大脑进行控制
-    pub fn car_dying(&mut self, score: i32) -> Option<String>{
-        //设置网络得分
-        self.ga.fitness_scores()[self.current_brain] = self.frame_count+score as f64*10.;
-        if self.frame_count>self.max_score{
-            self.max_score = self.frame_count;
+    //设置训练使用的随机数种子，每一代的游戏世界由它和代数决定
+    pub fn set_seed(&mut self, seed: u64){
+        self.seed = seed;
+    }
+
+    //设置每个网络玩的局数和得分的合并方式
+    pub fn set_episodes(&mut self, episodes: usize, aggregation: Aggregation){
+        //局数改变时当前网络已经玩完的各局作废
+        if episodes.max(1) != self.episodes{
+            self.episode_scores.clear();
+            self.episode_behaviours.clear();
         }
+        self.episodes = episodes.max(1);
+        self.aggregation = aggregation;
+    }
+
+    pub fn aggregation(&self) -> Aggregation{
+        self.aggregation
+    }
+
+    //这一代每一局游戏世界的种子，同一代的所有网络使用相同的种子
+    pub fn episode_seeds(&self) -> Vec<u64>{
+        let mut rng = Random::new(self.seed ^ self.generation as u64);
+        (0..self.episodes).map(|_| rng.next_u64()).collect()
+    }
+
+    //当前网络下一局游戏世界的种子
+    pub fn episode_seed(&self) -> u64{
+        self.episode_seeds()[self.episode_scores.len()]
+    }
+
+    //当前一代所有网络的副本
+    pub fn phenotypes(&mut self) -> Vec<NeuralNet>{
+        (0..self.ga.pop_size() as usize)
+            .map(|i| self.ga.get_phenotype(i).clone())
+            .collect()
+    }
+
+    pub fn limits(&self) -> &EpisodeLimits{
+        &self.limits
+    }
+
+    //并行评估当前一代的所有网络(每个网络使用新的游戏世界玩多局)，然后进入下一代
+    pub fn train_generation(&mut self){
+        while !self.train_slice(self.ga.pop_size() as usize){}
+    }
+
+    //并行评估当前一代中接下来的count个网络，所有网络都评估完后进入下一代并返回true
+    //窗口程序每一帧只评估一部分网络，避免长时间没有响应
+    pub fn train_slice(&mut self, count: usize) -> bool{
+        let mut pending = match self.pending.take(){
+            Some(pending) => pending,
+            None => PendingGeneration{
+                nets: self.phenotypes(),
+                seeds: self.episode_seeds(),
+                results: vec![],
+            },
+        };
+        let start = pending.results.len();
+        let end = (start + count.max(1)).min(pending.nets.len());
+        let results = training::evaluate_population(&mut pending.nets[start..end], &self.sensors, &self.world_config(), &self.limits, &pending.seeds);
+        pending.results.extend(results);
+        if pending.results.len() < pending.nets.len(){
+            self.pending = Some(pending);
+            return false;
+        }
+        self.finish_generation(&pending.results);
+        true
+    }
+
+    //设置每个网络各局的结果(按网络顺序)，然后进入下一代
+    pub fn finish_generation(&mut self, results: &[Vec<EpisodeStats>]){
+        self.episode_ends = results.iter().flatten().map(|stats| stats.end).collect();
+        for (i, episodes) in results.iter().enumerate(){
+            let scores: Vec<f64> = episodes.iter().map(|stats| self.fitness.fitness(stats)).collect();
+            self.set_fitness_score(i, self.aggregation.aggregate(&scores));
+            let behaviours: Vec<Vec<f64>> = episodes.iter().map(|stats| stats.behaviour.descriptor()).collect();
+            self.set_behaviour(i, novelty::mean_descriptor(&behaviours));
+        }
+        self.episode = EpisodeStats::default();
+        self.monitor = EpisodeMonitor::default();
+        self.episode_end = None;
+        self.episode_scores.clear();
+        self.episode_behaviours.clear();
+        self.epoch()
+    }
+
+    //选择网络得分的计算方式
+    pub fn set_fitness(&mut self, fitness: Box<dyn FitnessFunction>){
+        self.fitness = fitness;
+    }
+
+    pub fn fitness(&self) -> &dyn FitnessFunction{
+        self.fitness.as_ref()
+    }
+
+    //设置网络得分
+    fn set_fitness_score(&mut self, index: usize, score: f64){
+        self.ga.fitness_scores()[index] = score;
+        if score>self.max_score{
+            self.max_score = score;
+        }
+    }
+
+    //记录当前网络这一局中发生的事件
+    pub fn record(&mut self, events: &[GameEvent]){
+        self.episode.record(events);
+    }
+
+    //记录当前网络这一帧汽车的位置，并检查这一局是否超时或停滞
+    pub fn record_position(&mut self, x: f64){
+        self.episode.behaviour.record_position(x);
+        self.episode_end = self.monitor.update(&self.limits, &self.episode, x);
+    }
+
+    //汽车被子弹击中(或这一局超时、停滞)以后，当前网络玩完所有局后进入下一个大脑进行控制
+    //调用者需要用episode_seed()创建新的游戏世界
+    pub fn car_dying(&mut self){
+        self.episode.end = self.episode_end.take().unwrap_or(EpisodeEnd::CarHit);
+        self.episode_ends.push(self.episode.end);
+        self.monitor = EpisodeMonitor::default();
+        let score = self.fitness.fitness(&self.episode);
+        self.episode_behaviours.push(self.episode.behaviour.descriptor());
+        self.episode = EpisodeStats::default();//重置网络得分
+        self.episode_scores.push(score);
+        if self.episode_scores.len() < self.episodes{
+            return;
+        }
+        //设置网络得分
+        let score = self.aggregation.aggregate(&self.episode_scores);
+        self.episode_scores.clear();
+        self.set_fitness_score(self.current_brain, score);
+        let behaviour = novelty::mean_descriptor(&self.episode_behaviours);
+        self.episode_behaviours.clear();
+        self.set_behaviour(self.current_brain, behaviour);
         self.current_brain += 1;
-        self.frame_count = 0.0;//重置网络得分
         //所有网络都已死亡，进入下一代
         if self.current_brain == self.ga.pop_size() as usize{
-            self.epoch()
-        }else{
-            None
+            self.epoch();
         }
     }
 
     //更新网络得到输出
-    //输入：车子位置(x/width), 最近的外星人位置(x/width, y/height)，最近的子弹位置(x/width, y/height)
-    pub fn update(&mut self, car_pos: f64, alien_x: f64, alien_y: f64, missile_x: f64, missile_y: f64) -> (Turn, bool){
-        self.frame_count += 1.0;
+    //输入：由传感器配置从模拟中读取的观测值(见sensors.rs)
+    pub fn update(&mut self, inputs: &[f64]) -> Action{
+        self.episode.frames += 1;
         //网络处理
-        let output = if self.use_best && self.best_brain.is_some(){
-            self.best_brain.as_mut().unwrap().update(&[car_pos, missile_x, missile_y], RunType::Active)
-        }else{
-            let phenotype = self.ga.get_phenotype(self.current_brain);
-            phenotype.update(&[car_pos, missile_x, missile_y], RunType::Active)
+        let net = match self.best_brain.as_mut(){
+            Some(best) if self.use_best => best,
+            _ => self.ga.get_phenotype(self.current_brain),
         };
-        let fire = output[1] > 0.5;
-        if output[0] > 0.5 {
-            (Turn::Left, fire)
-        }else{
-            (Turn::Right, fire)
-        }
+        let output = net.update(inputs, RunType::Active);
+        //记录这一帧的激活值，用于显示
+        self.activations.inputs = inputs.to_vec();
+        self.activations.hidden = net.neurons.iter()
+            .filter(|neuron| neuron.neuron_type == NeuronType::Hidden)
+            .map(|neuron| neuron.output)
+            .collect();
+        self.activations.outputs = output.clone();
+        decide(&output, self.sensors.actions)
+    }
+
+    //上一帧网络的输入、隐藏神经元和输出的激活值
+    pub fn activations(&self) -> &Activations{
+        &self.activations
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn resume_restores_the_population(){
+        let path = std::env::temp_dir().join(format!("spaceout-resume-{}.json", std::process::id()));
+        let mut brain = CarBrain::with_population(SensorConfig::default(), 10);
+        brain.generation = 7;
+        brain.current_brain = 3;
+        brain.episode_scores = vec![120.0];
+        brain.set_novelty(Some(0.5));
+        brain.save_checkpoint(&path).unwrap();
+        let resumed = CarBrain::resume(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.generation, 7);
+        assert_eq!(resumed.current_brain, 3);
+        assert_eq!(resumed.episode_scores, vec![120.0]);
+        assert_eq!(resumed.novelty_archive_size(), Some(0));
+        assert_eq!(
+            serde_json::to_value(SavedGA::from_ga(&resumed.ga)).unwrap(),
+            serde_json::to_value(SavedGA::from_ga(&brain.ga)).unwrap()
+        );
+    }
+}
@@ -1,55 +1,26 @@
-use mengine::engine::{Resource, Sprite, SpriteExt, BA_DIE, SA_ADDSPRITE, SPRITEACTION};
-use mengine::*;
-use std::cell::RefCell;
-use std::rc::Rc;
+use super::simulation::{Alien, Body, BoundsAction, SpriteKind};
+use super::random::Random;
+use mengine::Rect;
 
-//外星人精灵扩展
+//外星人规则
 
-pub struct AlienSprite {
-    pub img_bmissile: Image,
-    pub img_jmissile: Image,
-    pub img_tmissile: Image,
-    pub difficulty: Rc<RefCell<i32>>,
+//检查外星人是否要发射子弹
+pub fn wants_to_fire(rng: &mut Random, difficulty: i32) -> bool {
+    rng.rand_int(0, difficulty / 2) == 0
 }
 
-impl SpriteExt for AlienSprite {
-    fn update(&self, sprite_action: SPRITEACTION) -> SPRITEACTION {
-        //检查精灵是否要发射子弹
-        match rand_int(0, *self.difficulty.borrow() / 2) {
-            0 => sprite_action | SA_ADDSPRITE,
-            _ => sprite_action,
-        }
-        // sprite_action
-    }
+//创建一个新的子弹精灵
+pub fn create_missile(id: usize, alien: Alien, sprite: &Body) -> Body {
+    let bounds = Rect::new(0.0, 0.0, 640.0, 410.0);
+    let pos = sprite.position();
 
-    fn add_sprite(&self, sprite: &Sprite) -> Sprite {
-        //创建一个新的子弹精灵
-        let bounds = Rect::new(0.0, 0.0, 640.0, 410.0);
-        let pos = sprite.position();
-        let mut velocity = Point { x: 0.0, y: 0.0 };
-
-        let missile_bitmap = match sprite.name() {
-            "blobbo" => {
-                velocity.y = 7.0;
-                self.img_bmissile.clone()
-            }
-            "jelly" => {
-                velocity.y = 5.0;
-                self.img_jmissile.clone()
-            }
-            _ => {
-                velocity.y = 3.0;
-                self.img_tmissile.clone()
-            }
-        };
-        let mut sub_sprite = Sprite::with_bounds_action(
-            String::from("amissile"),
-            Resource::Static(missile_bitmap),
-            bounds,
-            BA_DIE,
-        );
-        sub_sprite.set_velocity(velocity.x, velocity.y);
-        sub_sprite.set_position(pos.left + sprite.width() / 2.0, pos.bottom);
-        sub_sprite
-    }
+    let velocity_y = match alien {
+        Alien::Blobbo => 7.0,
+        Alien::Jelly => 5.0,
+        Alien::Timmy => 3.0,
+    };
+    let mut sub_sprite = Body::new(id, SpriteKind::AlienMissile(alien), bounds, BoundsAction::Die);
+    sub_sprite.set_velocity(0.0, velocity_y);
+    sub_sprite.set_position(pos.left + sprite.width() / 2.0, pos.bottom);
+    sub_sprite
 }
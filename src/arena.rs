@@ -0,0 +1,107 @@
+use super::ai::{decide, CarBrain};
+use super::fitness::{EpisodeEnd, EpisodeStats};
+use super::simulation::{GameEvent, StepResult, World};
+use super::stagnation::EpisodeMonitor;
+use neat::phenotype::{NeuralNet, RunType};
+
+//整个种群同时训练：这一代的每个网络驾驶一辆汽车，所有汽车共享同一群外星人，
+//汽车被击中(或超时、停滞)后退出，最后一辆汽车退出时这一局结束，所有网络玩完各局后进入下一代
+
+pub struct Arena {
+    world: World,
+    nets: Vec<NeuralNet>,
+    //每辆汽车这一局的统计和停滞检测
+    stats: Vec<EpisodeStats>,
+    monitors: Vec<EpisodeMonitor>,
+    //每个网络已经玩完的各局结果
+    results: Vec<Vec<EpisodeStats>>,
+}
+
+impl Arena {
+    //用当前一代的所有网络开始第一局
+    pub fn new(brain: &mut CarBrain) -> Arena {
+        let nets = brain.phenotypes();
+        let count = nets.len();
+        Arena {
+            world: World::with_cars(brain.episode_seeds()[0], brain.world_config(), count),
+            nets,
+            stats: vec![EpisodeStats::default(); count],
+            monitors: vec![EpisodeMonitor::default(); count],
+            results: vec![vec![]; count],
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    //模拟一帧，所有还在场上的汽车各自由自己的网络驾驶
+    pub fn step(&mut self, brain: &mut CarBrain) -> StepResult {
+        let mut result = self.world.update();
+        self.record(&result.events);
+        for event in &result.events {
+            if let GameEvent::CarHit { car, .. } = *event {
+                self.finish(car, EpisodeEnd::CarHit);
+            }
+        }
+
+        let sensors = brain.sensors().clone();
+        let limits = brain.limits().clone();
+        let mut events = vec![];
+        for i in 0..self.nets.len() {
+            if !self.world.car_alive(i) {
+                continue;
+            }
+            self.world.select_car(i);
+            let car_x = self.world.car().center().x as f64;
+            if let Some(end) = self.monitors[i].update(&limits, &self.stats[i], car_x) {
+                self.world.remove_car(i);
+                self.finish(i, end);
+                continue;
+            }
+            self.stats[i].frames += 1;
+            self.stats[i].behaviour.record_position(car_x);
+            let inputs = sensors.observe(&self.world);
+            let action = decide(&self.nets[i].update(&inputs, RunType::Active), sensors.actions);
+            self.world.drive(action, &mut events);
+        }
+        self.record(&events);
+        result.events.append(&mut events);
+
+        match (0..self.nets.len()).find(|&i| self.world.car_alive(i)) {
+            //car()等方法使用第一辆还在场上的汽车
+            Some(i) => self.world.select_car(i),
+            None => self.next_episode(brain),
+        }
+        result
+    }
+
+    //把事件记录到所属汽车的统计中
+    fn record(&mut self, events: &[GameEvent]) {
+        for event in events {
+            if let Some(car) = event.car() {
+                self.stats[car].record(std::slice::from_ref(event));
+            }
+        }
+    }
+
+    fn finish(&mut self, car: usize, end: EpisodeEnd) {
+        self.stats[car].end = end;
+        self.results[car].push(self.stats[car]);
+    }
+
+    //最后一辆汽车退出：开始下一局，或者设置得分进入下一代
+    fn next_episode(&mut self, brain: &mut CarBrain) {
+        let seeds = brain.episode_seeds();
+        let episode = self.results[0].len();
+        if episode < seeds.len() {
+            let count = self.nets.len();
+            self.world = World::with_cars(seeds[episode], brain.world_config(), count);
+            self.stats = vec![EpisodeStats::default(); count];
+            self.monitors = vec![EpisodeMonitor::default(); count];
+        } else {
+            brain.finish_generation(&self.results);
+            *self = Arena::new(brain);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::rand_int;
+use super::random::Random;
 use mengine::*;
 use std::cmp;
 
@@ -11,17 +11,18 @@ pub struct StarryBackground {
     twink_delay: i32,
     stars: [Point; 100],
     star_colors: [(u8, u8, u8); 100],
+    rng: Random,
 }
 
 impl StarryBackground {
-    pub fn new(width: f64, height: f64, num_stars: usize, twink_delay: i32) -> StarryBackground {
+    pub fn new(width: f64, height: f64, num_stars: usize, twink_delay: i32, mut rng: Random) -> StarryBackground {
         let num_stars = cmp::min(num_stars, 100);
         //创建星星
         let mut stars = [Point { x: 0., y: 0. }; 100];
         let mut star_colors = [(255, 255, 255); 100];
         for i in 0..num_stars {
-            stars[i].x = rand_int(0, width as i32) as f64;
-            stars[i].y = rand_int(0, height as i32) as f64;
+            stars[i].x = rng.rand_int(0, width as i32) as f64;
+            stars[i].y = rng.rand_int(0, height as i32) as f64;
             star_colors[i] = (128, 128, 128);
         }
         StarryBackground {
@@ -31,18 +32,19 @@ impl StarryBackground {
             twink_delay,
             stars,
             star_colors,
+            rng,
         }
     }
 
-    pub fn default(width: f64, height: f64) -> StarryBackground {
-        StarryBackground::new(width, height, 100, 50)
+    pub fn default(width: f64, height: f64, rng: Random) -> StarryBackground {
+        StarryBackground::new(width, height, 100, 50, rng)
     }
 
     pub fn update(&mut self) {
         //随机改变星星的颜色以使其闪烁
         for i in 0..self.num_stars {
-            if rand_int(0, self.twink_delay) == 0 {
-                let color = rand_int(0, 256) as u8;
+            if self.rng.rand_int(0, self.twink_delay) == 0 {
+                let color = self.rng.rand_int(0, 256) as u8;
                 self.star_colors[i] = (color, color, color);
             }
         }
@@ -0,0 +1,64 @@
+use spaceout::config;
+use spaceout::controller::ScriptedController;
+use spaceout::imitation::{Dataset, ImitationModel};
+use spaceout::options::Options;
+use spaceout::training;
+use std::process;
+use std::time::Instant;
+
+//模仿学习：用--record记录的人类驾驶数据拟合网络，保存后可以用--imitate初始化NEAT种群
+//cargo run --release --bin spaceout-imitate -- --dataset demo.jsonl --imitate imitation.json --benchmark 200
+
+//没有指定--benchmark时比较的局数
+const DEFAULT_BENCHMARK_EPISODES: usize = 100;
+
+fn main() {
+    let options = Options::from_args();
+    let config = config::load_or_exit(&options);
+    let seed = config.seed.unwrap_or_else(training::default_seed);
+    println!("随机数种子:{}", seed);
+
+    let path = options.dataset.as_ref().unwrap_or_else(|| {
+        println!("需要用--dataset指定示范数据文件");
+        process::exit(1);
+    });
+    let dataset = Dataset::load(path).unwrap_or_else(|err| {
+        println!("加载示范数据失败:{:?} {:?}", path, err);
+        process::exit(1);
+    });
+    if dataset.samples.is_empty() {
+        println!("示范数据中没有记录:{:?}", path);
+        process::exit(1);
+    }
+    if dataset.sensors != config.sensors {
+        println!("示范数据的传感器配置和当前配置不同，训练时需要使用记录时的配置");
+    }
+    println!(
+        "示范数据:{} 帧数:{} 训练轮数:{} 学习率:{}",
+        path.display(),
+        dataset.samples.len(),
+        config.imitation.epochs,
+        config.imitation.learning_rate
+    );
+
+    let start = Instant::now();
+    let model = ImitationModel::fit(&dataset, &config.imitation, seed);
+    let (turn, fire) = model.accuracy(&dataset);
+    println!(
+        "拟合完成 转向正确率:{:.1}% 开火正确率:{:.1}% 用时:{:.1}秒",
+        turn * 100.0,
+        fire * 100.0,
+        start.elapsed().as_secs_f64()
+    );
+    if let Some(path) = options.imitate.as_ref() {
+        match model.save(path) {
+            Ok(()) => println!("模仿网络已保存:{:?}", path),
+            Err(err) => println!("保存模仿网络失败:{:?} {:?}", path, err),
+        }
+    }
+
+    //和手写规则在相同的种子上比较
+    let comparison = config.comparison(options.benchmark.unwrap_or(DEFAULT_BENCHMARK_EPISODES), seed);
+    comparison.run("imitation", || Box::new(model.clone()));
+    comparison.run("scripted", || Box::new(ScriptedController));
+}
@@ -0,0 +1,66 @@
+use spaceout::config;
+use spaceout::controller::ScriptedController;
+use spaceout::learner::{Agent, Learner, LEARNER_NAMES};
+use spaceout::options::Options;
+use spaceout::random::Random;
+use spaceout::training;
+use std::process;
+use std::time::Instant;
+
+//用Q-learning或DQN训练，然后和手写规则(以及--load-brain加载的NEAT网络)在相同的种子上比较
+//cargo run --release --bin spaceout-rl -- --learner dqn --train-episodes 5000 --benchmark 500 --seed 1
+
+//没有指定--benchmark时比较的局数
+const DEFAULT_BENCHMARK_EPISODES: usize = 100;
+//每隔多少局打印一次训练进度
+const PRINT_EVERY: usize = 100;
+
+fn main() {
+    let options = Options::from_args();
+    let config = config::load_or_exit(&options);
+    let seed = config.seed.unwrap_or_else(training::default_seed);
+    println!("随机数种子:{}", seed);
+
+    let mut agent = Agent::new(config.learner.clone(), config.env_config(), seed).unwrap_or_else(|| {
+        println!("未知的学习器:{} 可选:{:?}", config.learner.kind, LEARNER_NAMES);
+        process::exit(1);
+    });
+    println!("学习器:{} 训练局数:{}", config.learner.kind, config.learner.episodes);
+
+    //训练使用另一组种子，比较时的种子与spaceout --benchmark相同
+    let mut seeds = Random::new(!seed);
+    let start = Instant::now();
+    let (mut frames, mut kills, mut reward) = (0.0, 0.0, 0.0);
+    for episode in 1..=config.learner.episodes {
+        let (stats, total) = agent.train_episode(seeds.next_u64());
+        frames += stats.frames as f64;
+        kills += stats.aliens_killed as f64;
+        reward += total;
+        if episode % PRINT_EVERY == 0 || episode == config.learner.episodes {
+            let count = (episode - 1) % PRINT_EVERY + 1;
+            let states = match agent.learner() {
+                Learner::Table(table) => format!(" 状态数:{}", table.states()),
+                Learner::Dqn(_) => String::new(),
+            };
+            println!(
+                "第{}局 平均存活帧数:{:.1} 平均击落:{:.2} 平均奖励:{:.2} 探索率:{:.3}{} 用时:{:.1}秒",
+                episode,
+                frames / count as f64,
+                kills / count as f64,
+                reward / count as f64,
+                agent.epsilon(),
+                states,
+                start.elapsed().as_secs_f64()
+            );
+            frames = 0.0;
+            kills = 0.0;
+            reward = 0.0;
+        }
+    }
+
+    let comparison = config.comparison(options.benchmark.unwrap_or(DEFAULT_BENCHMARK_EPISODES), seed);
+    let policy = agent.policy();
+    comparison.run(&config.learner.kind, || Box::new(policy.clone()));
+    comparison.run("scripted", || Box::new(ScriptedController));
+    comparison.run_network(options.load_brain.as_ref(), &config.sensors);
+}
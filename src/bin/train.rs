@@ -0,0 +1,101 @@
+use spaceout::ai::CarBrain;
+use spaceout::config;
+use spaceout::options::Options;
+use spaceout::training;
+use std::process;
+use std::time::Instant;
+
+//命令行训练程序：不打开窗口，并行训练指定的代数或者直到达到目标得分，适合在没有显示器的服务器上运行
+//cargo run --release --bin spaceout-train -- --generations 500 --checkpoint pop.json --save-brain best.json
+
+//没有指定--generations和--target-fitness时训练的代数
+const DEFAULT_GENERATIONS: i32 = 100;
+
+fn main() {
+    let options = Options::from_args();
+    let config = config::load_or_exit(&options);
+    let seed = config.seed.unwrap_or_else(training::default_seed);
+    println!("随机数种子:{}", seed);
+
+    let mut brain = match options.resume.as_ref() {
+        //从检查点恢复失败时退出，不要覆盖原来的检查点
+        Some(path) => CarBrain::resume(path).unwrap_or_else(|err| {
+            println!("加载检查点失败:{:?} {:?}", path, err);
+            process::exit(1);
+        }),
+        None => config.create_brain(),
+    };
+    config.configure(&mut brain, &options, seed);
+
+    let generations = match (options.generations, options.target_fitness) {
+        (Some(generations), _) => Some(generations),
+        (None, Some(_)) => None,
+        (None, None) => Some(DEFAULT_GENERATIONS),
+    };
+    let first_generation = brain.current_generation();
+    println!(
+        "从第{}代开始训练 代数:{} 目标得分:{} 种群数量:{} 得分方式:{} 每个网络的局数:{}",
+        first_generation,
+        generations.map_or("不限".to_string(), |n| n.to_string()),
+        options.target_fitness.map_or("无".to_string(), |f| f.to_string()),
+        config.population,
+        brain.fitness().name(),
+        config.episodes
+    );
+
+    let start = Instant::now();
+    let mut reached_target = false;
+    while generations.is_none_or(|n| brain.current_generation() - first_generation < n) {
+        let generation_start = Instant::now();
+        brain.train_generation();
+        let stats = brain.last_stats().cloned().unwrap_or_default();
+        let stage = brain
+            .curriculum_stage()
+            .map(|(_, stage)| format!(" 课程阶段:{}", stage.name))
+            .unwrap_or_default();
+        println!(
+            "第{}代 最高:{:.1} 平均:{:.1} 中位数:{:.1} 最低:{:.1} 物种:{} 平均连接数:{:.1} 超时:{} 停滞:{} 用时:{:.2}秒{}",
+            stats.generation,
+            stats.best,
+            stats.mean,
+            stats.median,
+            stats.worst,
+            stats.species,
+            stats.mean_links,
+            stats.timeouts,
+            stats.stagnations,
+            generation_start.elapsed().as_secs_f64(),
+            stage
+        );
+        if options.target_fitness.is_some_and(|target| stats.best >= target) {
+            reached_target = true;
+            break;
+        }
+    }
+
+    //结束时总是保存一次检查点，下次可以用--resume继续
+    if let Some(path) = options.checkpoint.as_ref() {
+        match brain.save_checkpoint(path) {
+            Ok(()) => println!("检查点已保存:{:?}", path),
+            Err(err) => println!("保存检查点失败:{:?} {:?}", path, err),
+        }
+    }
+    if let Some(path) = options.save_brain.as_ref() {
+        match brain.save_best(path) {
+            Ok(()) => println!("最好的网络已保存:{:?}", path),
+            Err(err) => println!("保存网络失败:{:?} {:?}", path, err),
+        }
+    }
+
+    let trained = brain.current_generation() - first_generation;
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "训练结束({}) 训练代数:{} 当前代数:{} 最高得分:{} 总用时:{:.1}秒 平均每代:{:.2}秒",
+        if reached_target { "达到目标得分" } else { "达到训练代数" },
+        trained,
+        brain.current_generation(),
+        brain.max_score(),
+        elapsed,
+        elapsed / trained.max(1) as f64
+    );
+}
@@ -0,0 +1,277 @@
+use super::ai::{ActionMode, CarBrain, ACTION_MODE_NAMES, POP_SIZE};
+use super::curriculum::Curriculum;
+use super::env::{EnvConfig, RewardConfig};
+use super::fitness::{Aggregation, FitnessConfig, AGGREGATION_NAMES, FITNESS_NAMES};
+use super::imitation::{ImitationConfig, ImitationModel};
+use super::learner::LearnerConfig;
+use super::mutation::WeightPerturbation;
+use super::options::Options;
+use super::persist::invalid_data;
+use super::sensors::SensorConfig;
+use super::simulation::WorldConfig;
+use super::stagnation::PopulationStagnation;
+use super::training::{Comparison, EpisodeLimits};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+//训练配置文件(.toml为TOML格式，其它为JSON)，不需要重新编译就可以调整实验参数
+//所有字段都可以省略，省略时使用默认值；命令行参数优先于配置文件，示例见training.example.toml
+//拼错或不存在的字段，以及会让游戏无法进行的值(见validate)会导致加载失败
+//neat库的变异率等参数是库中的常量，不能在这里配置；extra_weight_perturbation不改变neat的变异，只是每一代繁殖后再额外扰动一次连接权重
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrainingConfig {
+    //随机数种子，不指定时使用当前时间
+    pub seed: Option<u64>,
+    //种群数量
+    pub population: i32,
+    //网络的输入和输出
+    pub sensors: SensorConfig,
+    pub fitness: FitnessConfig,
+    //每个网络玩的局数和得分的合并方式(mean、min、trimmed)
+    pub episodes: usize,
+    pub aggregate: String,
+    //一局的最大帧数和停滞帧数
+    pub limits: EpisodeLimits,
+    //种群多代没有进步时重新生成种群(reseed)或加大变异(boost)
+    pub stagnation: PopulationStagnation,
+    //每一代繁殖后额外扰动连接权重的概率和幅度(不是neat的变异率)
+    pub extra_weight_perturbation: WeightPerturbation,
+    //不使用课程训练时的游戏世界难度(外星人、开火频率、导弹数量上限、开火冷却)
+    pub world: WorldConfig,
+    //课程训练的各个阶段
+    pub curriculum: Option<Curriculum>,
+    //新颖性搜索中得分所占的比例
+    pub novelty: Option<f64>,
+    pub checkpoint_every: i32,
+    //快速训练模式的更新频率(每秒帧数)，每一帧在每个CPU核心上评估一个网络
+    pub fast_mode_ups: u64,
+    //强化学习的奖励(外部驾驶者和spaceout-rl使用)
+    pub reward: RewardConfig,
+    //spaceout-rl使用的学习器
+    pub learner: LearnerConfig,
+    //spaceout-imitate的训练参数和写入初始种群时的扰动
+    pub imitation: ImitationConfig,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> TrainingConfig {
+        TrainingConfig {
+            seed: None,
+            population: POP_SIZE,
+            sensors: SensorConfig::default(),
+            fitness: FitnessConfig::default(),
+            episodes: 1,
+            aggregate: "mean".to_string(),
+            limits: EpisodeLimits::default(),
+            stagnation: PopulationStagnation::default(),
+            extra_weight_perturbation: WeightPerturbation::default(),
+            world: WorldConfig::default(),
+            curriculum: None,
+            novelty: None,
+            checkpoint_every: 10,
+            fast_mode_ups: 30,
+            reward: RewardConfig::default(),
+            learner: LearnerConfig::default(),
+            imitation: ImitationConfig::default(),
+        }
+    }
+}
+
+impl TrainingConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<TrainingConfig> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => {
+                toml::from_str(&text).map_err(|err| invalid_data(&err.to_string()))
+            }
+            _ => Ok(serde_json::from_str(&text)?),
+        }
+    }
+
+    //读取--config指定的配置文件，再用命令行参数覆盖其中的设置
+    //配置文件无法加载或设置无效时返回错误(包含文件名)，调用者应该退出而不是使用默认设置训练
+    pub fn from_options(options: &Options) -> io::Result<TrainingConfig> {
+        let with_path = |err: io::Error| match options.config.as_ref() {
+            Some(path) => io::Error::new(err.kind(), format!("{}: {}", path.display(), err)),
+            None => err,
+        };
+        let mut config = match options.config.as_ref() {
+            Some(path) => TrainingConfig::load(path).map_err(with_path)?,
+            None => TrainingConfig::default(),
+        };
+        config.apply_options(options);
+        config.validate().map_err(with_path)?;
+        Ok(config)
+    }
+
+    //检查会让训练或游戏无法正常进行的值
+    pub fn validate(&self) -> io::Result<()> {
+        if self.population < 1 {
+            return Err(invalid_data("population 至少为1"));
+        }
+        if self.episodes == 0 {
+            return Err(invalid_data("episodes 至少为1"));
+        }
+        if self.limits.max_frames == 0 {
+            return Err(invalid_data("limits.max_frames 为0时每一局在第一帧就结束"));
+        }
+        validate_world("world", &self.world)?;
+        if let Some(curriculum) = self.curriculum.as_ref() {
+            if curriculum.stages.is_empty() {
+                return Err(invalid_data("curriculum 至少需要一个阶段"));
+            }
+            for stage in curriculum.stages.iter() {
+                validate_world(&format!("curriculum 阶段 {} 的 world", stage.name), &stage.world)?;
+            }
+        }
+        Ok(())
+    }
+
+    //命令行参数覆盖配置文件中的设置
+    pub fn apply_options(&mut self, options: &Options) {
+        if options.seed.is_some() {
+            self.seed = options.seed;
+        }
+        if let Some(episodes) = options.episodes {
+            self.episodes = episodes;
+        }
+        if let Some(aggregate) = options.aggregate.as_ref() {
+            self.aggregate = aggregate.clone();
+        }
+        if let Some(fitness) = options.fitness.as_ref() {
+            self.fitness.kind = fitness.clone();
+        }
+        if options.curriculum && self.curriculum.is_none() {
+            self.curriculum = Some(Curriculum::default());
+        }
+        if options.novelty.is_some() {
+            self.novelty = options.novelty;
+        }
+        if let Some(name) = options.actions.as_ref() {
+            match ActionMode::from_name(name) {
+                Some(actions) => self.sensors.actions = actions,
+                None => log!("未知的动作模式:{} 可选:{:?}", name, ACTION_MODE_NAMES),
+            }
+        }
+        if options.sense_cooldown {
+            self.sensors.fire_cooldown = true;
+        }
+        if let Some(interval) = options.checkpoint_every {
+            self.checkpoint_every = interval;
+        }
+        if let Some(learner) = options.learner.as_ref() {
+            self.learner.kind = learner.clone();
+        }
+        if let Some(episodes) = options.train_episodes {
+            self.learner.episodes = episodes;
+        }
+    }
+
+    //在配置的游戏世界和结束条件下，用配置的得分函数比较驾驶者
+    pub fn comparison(&self, episodes: usize, seed: u64) -> Comparison<'_> {
+        Comparison::new(&self.world, &self.limits, self.fitness.build(), episodes, seed)
+    }
+
+    //强化学习环境的设置(与训练使用相同的网络输入、游戏世界和结束条件)
+    pub fn env_config(&self) -> EnvConfig {
+        EnvConfig {
+            sensors: self.sensors.clone(),
+            world: self.world.clone(),
+            limits: self.limits.clone(),
+            reward: self.reward.clone(),
+            pixels: None,
+        }
+    }
+
+    //按配置创建新的种群
+    pub fn create_brain(&self) -> CarBrain {
+        CarBrain::with_population(self.sensors.clone(), self.population)
+    }
+
+    //设置训练参数和输出文件(新建的或从检查点恢复的种群都使用)
+    pub fn configure(&self, brain: &mut CarBrain, options: &Options, seed: u64) {
+        brain.set_seed(seed);
+        let aggregation = Aggregation::from_name(&self.aggregate).unwrap_or_else(|| {
+            log!("未知的合并方式:{} 可选:{:?}", self.aggregate, AGGREGATION_NAMES);
+            Aggregation::default()
+        });
+        brain.set_episodes(self.episodes, aggregation);
+        match self.fitness.build() {
+            Some(fitness) => brain.set_fitness(fitness),
+            None => log!("未知的得分方式:{} 可选:{:?}", self.fitness.kind, FITNESS_NAMES),
+        }
+        brain.set_world_config(self.world.clone());
+        brain.set_limits(self.limits.clone());
+        brain.set_stagnation(self.stagnation.clone());
+        brain.set_weight_perturbation(self.extra_weight_perturbation.clone());
+        brain.set_curriculum(self.curriculum.clone());
+        brain.set_novelty(self.novelty);
+        if let Some(path) = options.stats.as_ref() {
+            if let Err(err) = brain.set_stats_path(path) {
+                log!("打开统计文件失败:{:?} {:?}", path, err);
+            }
+        }
+        brain.set_save_path(options.save_brain.clone());
+        brain.set_net_dir(options.net_dir.clone());
+        brain.set_checkpoint(options.checkpoint.clone(), self.checkpoint_every);
+        //从检查点恢复的种群不再初始化
+        if let (Some(path), None) = (options.imitate.as_ref(), options.resume.as_ref()) {
+            match ImitationModel::load(path).and_then(|model| brain.imitate(&model, self.imitation.noise)) {
+                Ok(()) => log!("已用模仿网络初始化种群:{:?}", path),
+                Err(err) => log!("加载模仿网络失败:{:?} {:?}", path, err),
+            }
+        }
+    }
+}
+
+//所有程序共用：读取配置文件和命令行参数，无法加载或设置无效时打印错误并退出
+pub fn load_or_exit(options: &Options) -> TrainingConfig {
+    TrainingConfig::from_options(options).unwrap_or_else(|err| {
+        log!("加载配置文件失败:{}", err);
+        process::exit(1);
+    })
+}
+
+//外星人用rand_int(0, difficulty / 2)决定是否开火，difficulty小于4时每一帧都会开火
+const MIN_DIFFICULTY: i32 = 4;
+
+fn validate_world(name: &str, world: &WorldConfig) -> io::Result<()> {
+    if world.difficulty < MIN_DIFFICULTY {
+        return Err(invalid_data(&format!(
+            "{}.difficulty 至少为{}，否则外星人每一帧都会开火",
+            name, MIN_DIFFICULTY
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_loads_and_typos_are_rejected() {
+        let config: TrainingConfig = toml::from_str(include_str!("../training.example.toml")).unwrap();
+        assert_eq!(config.seed, Some(1));
+        assert!(toml::from_str::<TrainingConfig>("mutation_rate = 0.5").is_err());
+        assert!(toml::from_str::<TrainingConfig>("[stagnation]\ngeneration = 10").is_err());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn values_that_break_the_game_are_rejected() {
+        let invalid = |text: &str| toml::from_str::<TrainingConfig>(text).unwrap().validate().is_err();
+        assert!(invalid("episodes = 0"));
+        assert!(invalid("population = 0"));
+        assert!(invalid("[limits]\nmax_frames = 0"));
+        assert!(invalid("[world]\ndifficulty = 1"));
+        assert!(invalid("[[curriculum.stages]]\nname = \"a\"\nmin_mean_fitness = 0.0\nworld = { difficulty = 2 }"));
+        assert!(!invalid("[world]\ndifficulty = 4"));
+    }
+}
@@ -0,0 +1,169 @@
+use super::ai::{decide, Action, CarBrain, Turn};
+use super::persist::SavedNet;
+use super::sensors::SensorConfig;
+use super::simulation::{GameEvent, SpriteKind, World, MAX_CAR_SPEED};
+use neat::phenotype::{NeuralNet, RunType};
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+//汽车驾驶者：根据游戏世界给出动作(转向或目标速度, 是否开火)，人类、网络和脚本使用同一个接口
+
+pub trait CarController {
+    fn name(&self) -> &'static str;
+    fn act(&mut self, world: &World) -> Action;
+    //这一帧发生的事件(需要计算奖励的驾驶者使用)
+    fn record(&mut self, _events: &[GameEvent]) {}
+    //游戏结束(非演示模式生命用完)，之后不会再调用act
+    fn game_over(&mut self, _world: &World) {}
+}
+
+//可以在运行时选择的驾驶者
+pub const DRIVER_NAMES: &[&str] = &["brain", "human", "network", "scripted", "external"];
+
+//正在训练的网络
+impl CarController for CarBrain {
+    fn name(&self) -> &'static str {
+        "brain"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let inputs = self.sensors().observe(world);
+        self.update(&inputs)
+    }
+}
+
+//向目标x坐标行驶，越接近速度越慢，到达后停下
+pub fn steer_towards(world: &World, x: f64) -> Turn {
+    let dx = x - world.car().center().x as f64;
+    if dx.abs() < 2.0 {
+        return Turn::Idle;
+    }
+    Turn::Target((dx / 10.0).clamp(-MAX_CAR_SPEED, MAX_CAR_SPEED))
+}
+
+//键盘和鼠标的状态，由窗口事件更新
+#[derive(Clone, Debug, Default)]
+pub struct HumanInput {
+    //鼠标的x坐标，汽车向它行驶
+    pub target_x: Option<f64>,
+    //方向键，优先于鼠标
+    pub left: bool,
+    pub right: bool,
+    //发射导弹，冷却中时等到可以开火再发射
+    pub fire: bool,
+}
+
+//人类玩家
+pub struct HumanController {
+    input: Rc<RefCell<HumanInput>>,
+}
+
+impl HumanController {
+    pub fn new(input: Rc<RefCell<HumanInput>>) -> HumanController {
+        HumanController { input }
+    }
+}
+
+impl CarController for HumanController {
+    fn name(&self) -> &'static str {
+        "human"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let mut input = self.input.borrow_mut();
+        let fire = input.fire && world.can_fire();
+        if fire {
+            input.fire = false;
+        }
+        let turn = if input.left && !input.right {
+            Turn::Left
+        } else if input.right && !input.left {
+            Turn::Right
+        } else {
+            //没有按键时向鼠标位置行驶，没有鼠标位置时停在原地
+            match input.target_x {
+                Some(x) => steer_towards(world, x),
+                None => Turn::Idle,
+            }
+        };
+        Action::new(turn, fire)
+    }
+}
+
+//从文件加载的网络，只驾驶不训练
+#[derive(Clone)]
+pub struct NetController {
+    net: NeuralNet,
+    sensors: SensorConfig,
+}
+
+impl NetController {
+    pub fn load<P: AsRef<Path>>(path: P, sensors: SensorConfig) -> io::Result<NetController> {
+        let net = SavedNet::load(path)?;
+        net.check_sensors(&sensors)?;
+        Ok(NetController {
+            net: net.to_net()?,
+            sensors,
+        })
+    }
+}
+
+impl CarController for NetController {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let inputs = self.sensors.observe(world);
+        decide(&self.net.update(&inputs, RunType::Active), self.sensors.actions)
+    }
+}
+
+//外星人子弹与汽车的水平距离小于它，并且在这个高度以内时需要躲避
+const DODGE_DISTANCE: f64 = 50.0;
+const DODGE_HEIGHT: f64 = 250.0;
+
+//手写规则：躲避最近的外星人子弹，否则开到最近的外星人下方，对准以后开火
+//作为进化出的网络的比较基准
+#[derive(Default)]
+pub struct ScriptedController;
+
+impl CarController for ScriptedController {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let car_pos = world.car().center();
+        let (car_x, car_y) = (car_pos.x as f64, car_pos.y as f64);
+        let nearest = |alien: bool| {
+            world
+                .bodies()
+                .iter()
+                .filter(|body| match body.kind() {
+                    SpriteKind::Alien(_) => alien,
+                    SpriteKind::AlienMissile(_) => !alien,
+                    _ => false,
+                })
+                .map(|body| body.center())
+                .min_by(|a, b| (*a - car_pos).len().partial_cmp(&(*b - car_pos).len()).unwrap())
+        };
+
+        //汽车上方有子弹落下时，向远离子弹的方向躲避
+        if let Some(missile) = nearest(false) {
+            let (dx, dy) = (missile.x as f64 - car_x, car_y - missile.y as f64);
+            if dx.abs() < DODGE_DISTANCE && dy > 0.0 && dy < DODGE_HEIGHT {
+                let away = if dx > 0.0 { car_x - DODGE_DISTANCE * 2.0 } else { car_x + DODGE_DISTANCE * 2.0 };
+                return Action::new(steer_towards(world, away), false);
+            }
+        }
+
+        match nearest(true) {
+            Some(alien) => {
+                let x = alien.x as f64;
+                //对准并且不在冷却中时开火
+                let aligned = (x - car_x).abs() < 10.0;
+                Action::new(steer_towards(world, x), aligned && world.can_fire())
+            }
+            None => Action::new(Turn::Idle, false),
+        }
+    }
+}
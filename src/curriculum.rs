@@ -0,0 +1,74 @@
+use super::simulation::{Alien, WorldConfig};
+use serde_derive::{Deserialize, Serialize};
+
+//课程训练：开始时只有少量慢速的外星人，平均得分达到门槛后进入下一阶段，
+//逐渐增加外星人数量、种类和开火频率
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CurriculumStage {
+    pub name: String,
+    //上一代的平均得分达到它时进入这个阶段
+    pub min_mean_fitness: f64,
+    pub world: WorldConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Curriculum {
+    pub stages: Vec<CurriculumStage>,
+}
+
+impl Default for Curriculum {
+    fn default() -> Curriculum {
+        let stage = |name: &str, min_mean_fitness, aliens, alien_types: &[Alien], difficulty| CurriculumStage {
+            name: name.to_string(),
+            min_mean_fitness,
+            world: WorldConfig {
+                aliens,
+                alien_types: alien_types.to_vec(),
+                difficulty,
+                ..WorldConfig::default()
+            },
+        };
+        Curriculum {
+            stages: vec![
+                //Timmy的子弹最慢(3像素/帧)
+                stage("入门", 0.0, 2, &[Alien::Timmy], 200),
+                stage("初级", 600.0, 3, &[Alien::Timmy, Alien::Jelly], 140),
+                stage("中级", 1200.0, 4, &[Alien::Timmy, Alien::Jelly, Alien::Blobbo], 100),
+                stage("高级", 2000.0, 6, &[Alien::Timmy, Alien::Jelly, Alien::Blobbo], 80),
+            ],
+        }
+    }
+}
+
+impl Curriculum {
+    //根据上一代的平均得分计算下一代的阶段，只前进不后退
+    pub fn next_stage(&self, current: usize, mean_fitness: f64) -> usize {
+        let mut stage = current;
+        while stage + 1 < self.stages.len() && mean_fitness >= self.stages[stage + 1].min_mean_fitness {
+            stage += 1;
+        }
+        stage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_advance_at_the_threshold_and_stop_at_the_last() {
+        //门槛为 0、600、1200、2000
+        let curriculum = Curriculum::default();
+        assert_eq!(curriculum.next_stage(0, 599.9), 0);
+        assert_eq!(curriculum.next_stage(0, 600.0), 1);
+        //得分足够时一次跳过多个阶段
+        assert_eq!(curriculum.next_stage(0, 1500.0), 2);
+        //只前进不后退
+        assert_eq!(curriculum.next_stage(2, 0.0), 2);
+        assert_eq!(curriculum.next_stage(3, 1e9), 3);
+        assert_eq!(curriculum.next_stage(0, 1e9), curriculum.stages.len() - 1);
+    }
+}
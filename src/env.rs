@@ -0,0 +1,262 @@
+use super::ai::{decide, Action};
+use super::fitness::{EpisodeEnd, EpisodeStats};
+use super::pixels::{FrameStack, PixelConfig};
+use super::sensors::SensorConfig;
+use super::simulation::{GameEvent, World, WorldConfig};
+use super::stagnation::EpisodeMonitor;
+use super::training::EpisodeLimits;
+use serde_derive::{Deserialize, Serialize};
+
+//强化学习环境(类似Gym)：reset(seed)开始新的一局，step(action)推进一帧，返回观测、奖励、是否结束和附加信息
+//观测和CarBrain看到的网络输入相同(见sensors.rs)，或者是缩小的灰度画面(见pixels.rs)，一局的规则和无窗口训练相同
+
+pub type Observation = Vec<f64>;
+
+//每一帧的奖励由这一帧发生的事件加权得到
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewardConfig {
+    //每存活一帧
+    pub survival: f64,
+    //击落一个外星人
+    pub kill: f64,
+    //发射一发导弹(为负数时惩罚乱开火)
+    pub fire: f64,
+    //外星人子弹从汽车旁边擦过
+    pub near_miss: f64,
+    //汽车被击中
+    pub hit: f64,
+}
+
+impl Default for RewardConfig {
+    fn default() -> RewardConfig {
+        RewardConfig {
+            survival: 0.01,
+            kill: 1.0,
+            fire: 0.0,
+            near_miss: 0.0,
+            hit: -1.0,
+        }
+    }
+}
+
+impl RewardConfig {
+    pub fn reward(&self, events: &[GameEvent]) -> f64 {
+        let mut reward = 0.0;
+        let mut hit = false;
+        for event in events {
+            reward += match event {
+                GameEvent::AlienKilled { .. } => self.kill,
+                GameEvent::MissileFired { .. } => self.fire,
+                GameEvent::NearMiss { .. } => self.near_miss,
+                GameEvent::CarHit { .. } => {
+                    hit = true;
+                    self.hit
+                }
+                _ => 0.0,
+            };
+        }
+        if !hit {
+            reward += self.survival;
+        }
+        reward
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EnvConfig {
+    //观测的内容和动作的含义
+    pub sensors: SensorConfig,
+    pub world: WorldConfig,
+    pub limits: EpisodeLimits,
+    pub reward: RewardConfig,
+    //设置时观测为最近几帧的灰度画面，而不是sensors的输入
+    pub pixels: Option<PixelConfig>,
+}
+
+//step返回的附加信息
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct StepInfo {
+    pub frame: u32,
+    pub score: i32,
+    pub kills: u32,
+    pub missiles_fired: u32,
+    pub near_misses: u32,
+    //这一局结束的原因，超时和停滞表示被截断而不是失败
+    pub end: Option<EpisodeEnd>,
+}
+
+pub struct SpaceOutEnv {
+    config: EnvConfig,
+    world: World,
+    stats: EpisodeStats,
+    monitor: EpisodeMonitor,
+    //上一个动作产生的事件，和下一帧的事件一起计算奖励
+    pending: Vec<GameEvent>,
+    //使用像素观测时最近几帧的画面
+    frames: Option<FrameStack>,
+    done: bool,
+}
+
+impl SpaceOutEnv {
+    //使用前需要先调用reset
+    pub fn new(config: EnvConfig) -> SpaceOutEnv {
+        SpaceOutEnv {
+            world: World::with_config(true, 0, config.world.clone()),
+            frames: config.pixels.clone().map(FrameStack::new),
+            config,
+            stats: EpisodeStats::default(),
+            monitor: EpisodeMonitor::default(),
+            pending: vec![],
+            done: true,
+        }
+    }
+
+    //在种子决定的新游戏世界中开始一局，相同的种子和相同的动作序列总是得到相同的结果
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.world = World::with_config(true, seed, self.config.world.clone());
+        self.stats = EpisodeStats::default();
+        self.monitor = EpisodeMonitor::default();
+        self.pending.clear();
+        self.done = false;
+        self.advance();
+        if let Some(frames) = self.frames.as_mut() {
+            frames.reset(&self.world);
+        }
+        self.observation()
+    }
+
+    //执行动作并推进一帧，一局结束后需要调用reset
+    pub fn step(&mut self, action: Action) -> (Observation, f64, bool, StepInfo) {
+        let reward = self.act(action);
+        (self.observation(), reward, self.done, self.info())
+    }
+
+    //和step相同，但不计算观测(驾驶者直接读取游戏世界时使用)
+    pub fn act(&mut self, action: Action) -> f64 {
+        if self.done {
+            return 0.0;
+        }
+        self.world.drive(action, &mut self.pending);
+        let events = self.advance();
+        if let Some(frames) = self.frames.as_mut() {
+            frames.push(&self.world);
+        }
+        self.config.reward.reward(&events)
+    }
+
+    //更新一帧并记录统计，检查汽车是否被击中、超时或停滞
+    fn advance(&mut self) -> Vec<GameEvent> {
+        let mut result = self.world.update();
+        let car_hit = result.car_hit();
+        result.events.append(&mut self.pending);
+        self.stats.record(&result.events);
+        if car_hit {
+            self.stats.end = EpisodeEnd::CarHit;
+            self.done = true;
+            return result.events;
+        }
+        let car_x = self.world.car().center().x as f64;
+        if let Some(end) = self.monitor.update(&self.config.limits, &self.stats, car_x) {
+            self.stats.end = end;
+            self.done = true;
+            return result.events;
+        }
+        self.stats.frames += 1;
+        self.stats.behaviour.record_position(car_x);
+        result.events
+    }
+
+    //当前的观测(与CarBrain的网络输入相同，或者叠加的灰度画面)
+    pub fn observation(&self) -> Observation {
+        match self.frames.as_ref() {
+            Some(frames) => frames.observation(),
+            None => self.config.sensors.observe(&self.world),
+        }
+    }
+
+    //使用像素观测时最近几帧的画面
+    pub fn frames(&self) -> Option<&FrameStack> {
+        self.frames.as_ref()
+    }
+
+    //把网络输出转换为动作(与CarBrain相同)
+    pub fn decide(&self, output: &[f64]) -> Action {
+        decide(output, self.config.sensors.actions)
+    }
+
+    pub fn info(&self) -> StepInfo {
+        StepInfo {
+            frame: self.stats.frames,
+            score: self.world.score(),
+            kills: self.stats.aliens_killed,
+            missiles_fired: self.stats.missiles_fired,
+            near_misses: self.stats.near_misses,
+            end: if self.done { Some(self.stats.end) } else { None },
+        }
+    }
+
+    pub fn observation_size(&self) -> usize {
+        match self.config.pixels.as_ref() {
+            Some(pixels) => pixels.num_inputs(),
+            None => self.config.sensors.num_inputs(),
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.done
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    //这一局到现在的统计
+    pub fn stats(&self) -> &EpisodeStats {
+        &self.stats
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Turn;
+    use crate::pixels::PixelConfig;
+
+    //按固定的动作序列玩一局，记录每一步的结果
+    fn play(env: &mut SpaceOutEnv, seed: u64) -> Vec<(Observation, f64, bool, StepInfo)> {
+        let mut steps = vec![(env.reset(seed), 0.0, false, env.info())];
+        for frame in 0..5000 {
+            let turn = if frame / 40 % 2 == 0 { Turn::Left } else { Turn::Right };
+            let step = env.step(Action::new(turn, frame % 5 == 0));
+            let done = step.2;
+            steps.push(step);
+            if done {
+                break;
+            }
+        }
+        steps
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_episode() {
+        let pixels = EnvConfig {
+            pixels: Some(PixelConfig::default()),
+            ..EnvConfig::default()
+        };
+        for config in [EnvConfig::default(), pixels] {
+            let mut env = SpaceOutEnv::new(config.clone());
+            let first = play(&mut env, 3);
+            assert!(first.last().unwrap().2);
+            //同一个环境重新开始，以及新的环境，结果都相同
+            assert_eq!(first, play(&mut env, 3));
+            assert_eq!(first, play(&mut SpaceOutEnv::new(config), 3));
+            assert_ne!(first, play(&mut env, 4));
+        }
+    }
+}
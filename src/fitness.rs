@@ -0,0 +1,314 @@
+use super::novelty::Behaviour;
+use super::simulation::GameEvent;
+use serde_derive::{Deserialize, Serialize};
+
+//网络得分的计算方式
+
+//一局游戏结束的原因
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EpisodeEnd {
+    //汽车被击中
+    #[default]
+    CarHit,
+    //达到最大帧数
+    Timeout,
+    //长时间没有击落外星人也没有移动
+    Stagnation,
+}
+
+//一局游戏的统计
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpisodeStats {
+    //存活帧数
+    pub frames: u32,
+    //击落的外星人数量
+    pub aliens_killed: u32,
+    //发射的导弹数量
+    pub missiles_fired: u32,
+    //从汽车旁边擦过的外星人子弹数量
+    pub near_misses: u32,
+    //汽车位置和开火时间，用于新颖性搜索
+    pub behaviour: Behaviour,
+    pub end: EpisodeEnd,
+}
+
+impl EpisodeStats {
+    //记录一帧中发生的事件
+    pub fn record(&mut self, events: &[GameEvent]) {
+        for event in events {
+            match event {
+                GameEvent::AlienKilled { .. } => self.aliens_killed += 1,
+                GameEvent::MissileFired { .. } => {
+                    self.missiles_fired += 1;
+                    self.behaviour.record_fire(self.frames);
+                }
+                GameEvent::NearMiss { .. } => self.near_misses += 1,
+                _ => (),
+            }
+        }
+    }
+}
+
+pub trait FitnessFunction: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn fitness(&self, stats: &EpisodeStats) -> f64;
+}
+
+//只看存活时间
+pub struct Survival;
+
+impl FitnessFunction for Survival {
+    fn name(&self) -> &'static str {
+        "survival"
+    }
+    fn fitness(&self, stats: &EpisodeStats) -> f64 {
+        stats.frames as f64
+    }
+}
+
+//只看击落的外星人
+pub struct Kills;
+
+impl FitnessFunction for Kills {
+    fn name(&self) -> &'static str {
+        "kills"
+    }
+    fn fitness(&self, stats: &EpisodeStats) -> f64 {
+        stats.aliens_killed as f64
+    }
+}
+
+//存活时间和击落数量加权
+pub struct WeightedBlend {
+    pub survival_weight: f64,
+    pub kill_weight: f64,
+}
+
+impl Default for WeightedBlend {
+    //与原来的 存活帧数+得分*10 相同(每个外星人25分)
+    fn default() -> WeightedBlend {
+        WeightedBlend {
+            survival_weight: 1.0,
+            kill_weight: 250.0,
+        }
+    }
+}
+
+impl FitnessFunction for WeightedBlend {
+    fn name(&self) -> &'static str {
+        "blend"
+    }
+    fn fitness(&self, stats: &EpisodeStats) -> f64 {
+        stats.frames as f64 * self.survival_weight + stats.aliens_killed as f64 * self.kill_weight
+    }
+}
+
+//加权得分，再惩罚没有击中的导弹
+pub struct AccuracyPenalised {
+    pub blend: WeightedBlend,
+    pub miss_penalty: f64,
+}
+
+impl Default for AccuracyPenalised {
+    fn default() -> AccuracyPenalised {
+        AccuracyPenalised {
+            blend: WeightedBlend::default(),
+            miss_penalty: 20.0,
+        }
+    }
+}
+
+impl FitnessFunction for AccuracyPenalised {
+    fn name(&self) -> &'static str {
+        "accuracy"
+    }
+    fn fitness(&self, stats: &EpisodeStats) -> f64 {
+        let misses = stats.missiles_fired.saturating_sub(stats.aliens_killed);
+        (self.blend.fitness(stats) - misses as f64 * self.miss_penalty).max(0.0)
+    }
+}
+
+//加权得分，再奖励躲过的子弹
+pub struct DodgeRewarded {
+    pub blend: WeightedBlend,
+    pub near_miss_reward: f64,
+}
+
+impl Default for DodgeRewarded {
+    fn default() -> DodgeRewarded {
+        DodgeRewarded {
+            blend: WeightedBlend::default(),
+            near_miss_reward: 50.0,
+        }
+    }
+}
+
+impl FitnessFunction for DodgeRewarded {
+    fn name(&self) -> &'static str {
+        "dodge"
+    }
+    fn fitness(&self, stats: &EpisodeStats) -> f64 {
+        self.blend.fitness(stats) + stats.near_misses as f64 * self.near_miss_reward
+    }
+}
+
+pub const FITNESS_NAMES: &[&str] = &["survival", "kills", "blend", "accuracy", "dodge"];
+
+//得分函数的种类和权重，可以写在训练配置文件中
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FitnessConfig {
+    //得分函数的名字，见FITNESS_NAMES
+    pub kind: String,
+    pub survival_weight: f64,
+    pub kill_weight: f64,
+    pub miss_penalty: f64,
+    pub near_miss_reward: f64,
+}
+
+impl Default for FitnessConfig {
+    fn default() -> FitnessConfig {
+        let blend = WeightedBlend::default();
+        FitnessConfig {
+            kind: "blend".to_string(),
+            survival_weight: blend.survival_weight,
+            kill_weight: blend.kill_weight,
+            miss_penalty: AccuracyPenalised::default().miss_penalty,
+            near_miss_reward: DodgeRewarded::default().near_miss_reward,
+        }
+    }
+}
+
+impl FitnessConfig {
+    pub fn build(&self) -> Option<Box<dyn FitnessFunction>> {
+        let blend = WeightedBlend {
+            survival_weight: self.survival_weight,
+            kill_weight: self.kill_weight,
+        };
+        match self.kind.as_str() {
+            "survival" => Some(Box::new(Survival)),
+            "kills" => Some(Box::new(Kills)),
+            "blend" => Some(Box::new(blend)),
+            "accuracy" => Some(Box::new(AccuracyPenalised {
+                blend,
+                miss_penalty: self.miss_penalty,
+            })),
+            "dodge" => Some(Box::new(DodgeRewarded {
+                blend,
+                near_miss_reward: self.near_miss_reward,
+            })),
+            _ => None,
+        }
+    }
+}
+
+//一个网络玩多局时，各局得分的合并方式
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Aggregation {
+    #[default]
+    Mean,
+    //最差的一局，奖励稳定的网络
+    Min,
+    //去掉最高和最低的一部分(比例)后取平均
+    TrimmedMean(f64),
+}
+
+pub const AGGREGATION_NAMES: &[&str] = &["mean", "min", "trimmed"];
+
+impl Aggregation {
+    pub fn from_name(name: &str) -> Option<Aggregation> {
+        match name {
+            "mean" => Some(Aggregation::Mean),
+            "min" => Some(Aggregation::Min),
+            //两端各去掉20%
+            "trimmed" => Some(Aggregation::TrimmedMean(0.2)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Mean => "mean",
+            Aggregation::Min => "min",
+            Aggregation::TrimmedMean(_) => "trimmed",
+        }
+    }
+
+    pub fn aggregate(&self, scores: &[f64]) -> f64 {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        let mean = |scores: &[f64]| scores.iter().sum::<f64>() / scores.len() as f64;
+        match *self {
+            Aggregation::Mean => mean(scores),
+            Aggregation::Min => scores.iter().cloned().fold(f64::INFINITY, f64::min),
+            Aggregation::TrimmedMean(trim) => {
+                let mut sorted = scores.to_vec();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let cut = (sorted.len() as f64 * trim) as usize;
+                //至少保留一局
+                let cut = cut.min((sorted.len() - 1) / 2);
+                mean(&sorted[cut..sorted.len() - cut])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(frames: u32, aliens_killed: u32, missiles_fired: u32, near_misses: u32) -> EpisodeStats {
+        EpisodeStats {
+            frames,
+            aliens_killed,
+            missiles_fired,
+            near_misses,
+            ..EpisodeStats::default()
+        }
+    }
+
+    #[test]
+    fn fitness_functions_weigh_the_episode_stats() {
+        let episode = stats(1000, 4, 10, 3);
+        assert_eq!(Survival.fitness(&episode), 1000.0);
+        assert_eq!(Kills.fitness(&episode), 4.0);
+        assert_eq!(WeightedBlend::default().fitness(&episode), 2000.0);
+        //6发导弹没有击中
+        assert_eq!(AccuracyPenalised::default().fitness(&episode), 2000.0 - 6.0 * 20.0);
+        assert_eq!(DodgeRewarded::default().fitness(&episode), 2000.0 + 3.0 * 50.0);
+        //惩罚后不会小于0
+        assert_eq!(AccuracyPenalised::default().fitness(&stats(10, 0, 100, 0)), 0.0);
+    }
+
+    #[test]
+    fn config_builds_every_named_fitness_function() {
+        for &name in FITNESS_NAMES {
+            let config = FitnessConfig {
+                kind: name.to_string(),
+                ..FitnessConfig::default()
+            };
+            assert_eq!(config.build().unwrap().name(), name);
+        }
+        let config = FitnessConfig {
+            kind: "speed".to_string(),
+            ..FitnessConfig::default()
+        };
+        assert!(config.build().is_none());
+    }
+
+    #[test]
+    fn aggregation_keeps_at_least_one_episode() {
+        let scores = [10.0, 100.0, 40.0, 30.0, 20.0];
+        assert_eq!(Aggregation::Mean.aggregate(&scores), 40.0);
+        assert_eq!(Aggregation::Min.aggregate(&scores), 10.0);
+        //两端各去掉一局
+        assert_eq!(Aggregation::TrimmedMean(0.2).aggregate(&scores), 30.0);
+        //局数比要去掉的少时至少保留中间的一局(偶数时两局)
+        assert_eq!(Aggregation::TrimmedMean(0.5).aggregate(&scores), 30.0);
+        assert_eq!(Aggregation::TrimmedMean(0.9).aggregate(&[10.0, 30.0]), 20.0);
+        assert_eq!(Aggregation::TrimmedMean(0.9).aggregate(&[10.0]), 10.0);
+        assert_eq!(Aggregation::Mean.aggregate(&[]), 0.0);
+    }
+}
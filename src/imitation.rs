@@ -0,0 +1,325 @@
+use super::ai::{decide, Action, ActionMode, Turn};
+use super::controller::CarController;
+use super::persist::invalid_data;
+use super::random::Random;
+use super::sensors::SensorConfig;
+use super::simulation::{World, MAX_CAR_SPEED};
+use neat::ga::GA;
+use neat::phenotype::NeuronType;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+//模仿学习：记录人类驾驶时每一帧的网络输入和动作，拟合一个没有隐藏层的网络，
+//用它驾驶汽车，或者把它的权重写入初始种群，让CarBrain从类似人类的行为开始进化
+
+//示范数据文件格式(JSON lines)：
+//  第一行: {"version":1,"sensors":{"car_x":true,"aliens":1,...}}   记录时使用的传感器配置
+//  之后每帧一行: {"observation":[0.52,0.31,...],"speed":-6.0,"fire":false}
+//  speed为汽车的目标速度(像素/帧)，左转为-最大速度，右转为最大速度，不动为0
+pub const DATASET_FILE_VERSION: u32 = 1;
+pub const MODEL_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct DatasetHeader {
+    version: u32,
+    sensors: SensorConfig,
+}
+
+//一帧的示范
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub observation: Vec<f64>,
+    pub speed: f64,
+    pub fire: bool,
+}
+
+impl Sample {
+    pub fn new(observation: Vec<f64>, action: Action) -> Sample {
+        let speed = match action.turn {
+            Turn::Left => -MAX_CAR_SPEED,
+            Turn::Right => MAX_CAR_SPEED,
+            Turn::Idle => 0.0,
+            Turn::Target(speed) => speed,
+        };
+        Sample {
+            observation,
+            speed,
+            fire: action.fire,
+        }
+    }
+
+    //网络应该给出的输出(0~1)，经过decide得到和示范相同的动作
+    pub fn targets(&self, mode: ActionMode) -> Vec<f64> {
+        let speed = (self.speed / MAX_CAR_SPEED).clamp(-1.0, 1.0);
+        let fire = if self.fire { 1.0 } else { 0.0 };
+        match mode {
+            //不动时左转和右转各一半
+            ActionMode::Binary => vec![(1.0 - speed) / 2.0, fire],
+            ActionMode::Idle => {
+                let left = if speed < -0.5 { 1.0 } else { 0.0 };
+                let right = if speed > 0.5 { 1.0 } else { 0.0 };
+                vec![left, right, fire]
+            }
+            ActionMode::Analog => vec![(speed + 1.0) / 2.0, fire],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Dataset {
+    pub sensors: SensorConfig,
+    pub samples: Vec<Sample>,
+}
+
+fn read_header<B: BufRead>(lines: &mut io::Lines<B>) -> io::Result<DatasetHeader> {
+    let header: DatasetHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(invalid_data("示范数据文件是空的")),
+    };
+    if header.version != DATASET_FILE_VERSION {
+        return Err(invalid_data(&format!("不支持的示范数据文件版本:{}", header.version)));
+    }
+    Ok(header)
+}
+
+impl Dataset {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Dataset> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = read_header(&mut lines)?;
+        let inputs = header.sensors.num_inputs();
+        let mut samples = vec![];
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sample: Sample = serde_json::from_str(&line)?;
+            if sample.observation.len() != inputs {
+                return Err(invalid_data(&format!("示范数据有{}个输入，传感器配置需要{}个", sample.observation.len(), inputs)));
+            }
+            samples.push(sample);
+        }
+        Ok(Dataset {
+            sensors: header.sensors,
+            samples,
+        })
+    }
+}
+
+//记录驾驶者的每一个动作，通常包装人类玩家
+pub struct Recorder<C: CarController> {
+    inner: C,
+    sensors: SensorConfig,
+    writer: BufWriter<File>,
+}
+
+impl<C: CarController> Recorder<C> {
+    //追加到已有的文件，已有文件的传感器配置必须相同
+    pub fn open<P: AsRef<Path>>(path: P, sensors: SensorConfig, inner: C) -> io::Result<Recorder<C>> {
+        let path = path.as_ref();
+        let is_new = !path.exists();
+        if !is_new {
+            let header = read_header(&mut BufReader::new(File::open(path)?).lines())?;
+            if header.sensors != sensors {
+                return Err(invalid_data("示范数据文件使用了不同的传感器配置"));
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            let header = DatasetHeader {
+                version: DATASET_FILE_VERSION,
+                sensors: sensors.clone(),
+            };
+            serde_json::to_writer(&mut writer, &header)?;
+            writeln!(writer)?;
+        }
+        Ok(Recorder { inner, sensors, writer })
+    }
+
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, sample)?;
+        writeln!(self.writer)?;
+        //窗口关闭时程序可能直接退出，每一帧都写入磁盘
+        self.writer.flush()
+    }
+}
+
+impl<C: CarController> CarController for Recorder<C> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let observation = self.sensors.observe(world);
+        let action = self.inner.act(world);
+        if let Err(err) = self.write(&Sample::new(observation, action)) {
+            log!("写入示范数据失败:{:?}", err);
+        }
+        action
+    }
+}
+
+//模仿训练的参数
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImitationConfig {
+    //训练轮数和学习率
+    pub epochs: usize,
+    pub learning_rate: f64,
+    //写入初始种群时给每个权重加上的随机扰动(-noise~noise)，保持种群的多样性
+    pub noise: f64,
+}
+
+impl Default for ImitationConfig {
+    fn default() -> ImitationConfig {
+        ImitationConfig {
+            epochs: 200,
+            learning_rate: 0.1,
+            noise: 0.1,
+        }
+    }
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+//没有隐藏层的网络：每个输出为输入加权和(加上偏置)的sigmoid，和初始种群的网络结构相同
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ImitationModel {
+    pub version: u32,
+    pub sensors: SensorConfig,
+    //每个输出一行，最后一个为偏置
+    pub weights: Vec<Vec<f64>>,
+}
+
+impl ImitationModel {
+    //用交叉熵损失和随机梯度下降拟合示范数据
+    pub fn fit(dataset: &Dataset, config: &ImitationConfig, seed: u64) -> ImitationModel {
+        let sensors = dataset.sensors.clone();
+        let (inputs, outputs) = (sensors.num_inputs(), sensors.num_outputs());
+        let mut model = ImitationModel {
+            version: MODEL_FILE_VERSION,
+            weights: vec![vec![0.0; inputs + 1]; outputs],
+            sensors,
+        };
+        let targets: Vec<Vec<f64>> = dataset
+            .samples
+            .iter()
+            .map(|sample| sample.targets(model.sensors.actions))
+            .collect();
+        let mut order: Vec<usize> = (0..dataset.samples.len()).collect();
+        let mut rng = Random::new(seed);
+        for _ in 0..config.epochs {
+            //每一轮打乱顺序
+            for i in (1..order.len()).rev() {
+                order.swap(i, rng.rand_int(0, i as i32 + 1) as usize);
+            }
+            for &index in &order {
+                let x = &dataset.samples[index].observation;
+                let output = model.outputs(x);
+                for (k, row) in model.weights.iter_mut().enumerate() {
+                    let error = output[k] - targets[index][k];
+                    for (w, x) in row.iter_mut().zip(x.iter().chain(Some(&1.0))) {
+                        *w -= config.learning_rate * error * x;
+                    }
+                }
+            }
+        }
+        model
+    }
+
+    pub fn outputs(&self, inputs: &[f64]) -> Vec<f64> {
+        self.weights
+            .iter()
+            .map(|row| sigmoid(row.iter().zip(inputs.iter().chain(Some(&1.0))).map(|(w, x)| w * x).sum()))
+            .collect()
+    }
+
+    //和示范动作相同的帧所占的比例(转向, 开火)
+    pub fn accuracy(&self, dataset: &Dataset) -> (f64, f64) {
+        if dataset.samples.is_empty() {
+            return (0.0, 0.0);
+        }
+        let mode = self.sensors.actions;
+        let (mut turn, mut fire) = (0, 0);
+        for sample in &dataset.samples {
+            let action = decide(&self.outputs(&sample.observation), mode);
+            let expected = decide(&sample.targets(mode), mode);
+            //binary模式下不动的示范任何方向都算正确
+            let ambiguous = mode == ActionMode::Binary && sample.speed == 0.0;
+            let same_turn = match (action.turn, expected.turn) {
+                (Turn::Target(a), Turn::Target(b)) => (a - b).abs() < MAX_CAR_SPEED / 4.0,
+                (a, b) => a == b,
+            };
+            if ambiguous || same_turn {
+                turn += 1;
+            }
+            if action.fire == expected.fire {
+                fire += 1;
+            }
+        }
+        let count = dataset.samples.len() as f64;
+        (turn as f64 / count, fire as f64 / count)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.into_inner()?.sync_all()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ImitationModel> {
+        let reader = BufReader::new(File::open(path)?);
+        let model: ImitationModel = serde_json::from_reader(reader)?;
+        if model.version != MODEL_FILE_VERSION {
+            return Err(invalid_data(&format!("不支持的模仿网络文件版本:{}", model.version)));
+        }
+        Ok(model)
+    }
+}
+
+//用拟合的网络驾驶
+impl CarController for ImitationModel {
+    fn name(&self) -> &'static str {
+        "imitation"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let inputs = self.sensors.observe(world);
+        decide(&self.outputs(&inputs), self.sensors.actions)
+    }
+}
+
+//把拟合的权重写入种群中每个基因组的输入(和偏置)到输出的连接，再加上随机扰动
+//neat的sigmoid斜率可能不同，但输出大于0.5的条件(加权和大于0)相同，所以binary和idle模式的动作不变
+pub fn seed_population(ga: &mut GA, model: &ImitationModel, noise: f64, rng: &mut Random) {
+    for genome in ga.genomes.iter_mut() {
+        let ids = |kind: NeuronType| -> Vec<i32> {
+            genome
+                .neurons
+                .iter()
+                .filter(|neuron| neuron.neuron_type == kind)
+                .map(|neuron| neuron.id)
+                .collect()
+        };
+        let (inputs, bias, outputs) = (ids(NeuronType::Input), ids(NeuronType::Bias), ids(NeuronType::Output));
+        for link in genome.links.iter_mut() {
+            let output = match outputs.iter().position(|&id| id == link.to_neuron) {
+                Some(output) => output,
+                None => continue,
+            };
+            let input = match inputs.iter().position(|&id| id == link.from_neuron) {
+                Some(input) => input,
+                //偏置的权重在每一行的最后
+                None if bias.contains(&link.from_neuron) => inputs.len(),
+                None => continue,
+            };
+            if let Some(&weight) = model.weights.get(output).and_then(|row| row.get(input)) {
+                link.weight = weight + (rng.next_f64() * 2.0 - 1.0) * noise;
+            }
+        }
+    }
+    ga.create_phenotypes();
+}
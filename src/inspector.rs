@@ -0,0 +1,54 @@
+use super::ai::Activations;
+use super::netdraw::dotted_line;
+use super::sensors::SensorConfig;
+use super::simulation::World;
+use mengine::Graphics;
+
+//网络激活值查看器：显示当前网络这一帧的输入、隐藏神经元和输出，以及汽车"看到"的目标
+
+const BAR_WIDTH: f64 = 50.0;
+const ROW_HEIGHT: f64 = 12.0;
+
+//画一行：名字和以中线为0的条形(-1~1)
+fn draw_bar(g: &mut Graphics, name: &str, value: f64, x: f64, y: f64) {
+    g.draw_text(name, x, y, &[255, 255, 255, 255], 10);
+    let center = x + 60.0 + BAR_WIDTH;
+    let len = value.clamp(-1.0, 1.0) * BAR_WIDTH;
+    g.fill_rect(&[60, 60, 60, 200], center - BAR_WIDTH, y + 2.0, BAR_WIDTH * 2.0, ROW_HEIGHT - 4.0);
+    let color = if value >= 0.0 { [0, 200, 0, 255] } else { [220, 0, 0, 255] };
+    g.fill_rect(&color, center.min(center + len), y + 2.0, len.abs(), ROW_HEIGHT - 4.0);
+    g.draw_text(&format!("{:.2}", value), center + BAR_WIDTH + 4.0, y, &[255, 255, 255, 255], 10);
+}
+
+//在(x, y)处绘制各层的激活值
+pub fn draw_activations(g: &mut Graphics, sensors: &SensorConfig, activations: &Activations, x: f64, y: f64) {
+    let mut rows: Vec<(String, f64)> = vec![];
+    let names = sensors.input_names();
+    for (name, &value) in names.iter().zip(&activations.inputs) {
+        rows.push((name.clone(), value));
+    }
+    for (i, &value) in activations.hidden.iter().enumerate() {
+        rows.push((format!("隐藏{}", i + 1), value));
+    }
+    for (name, &value) in sensors.actions.output_names().iter().zip(&activations.outputs) {
+        rows.push((format!("输出 {}", name), value));
+    }
+    let height = rows.len() as f64 * ROW_HEIGHT + 6.0;
+    g.fill_rect(&[0, 0, 0, 160], x, y, 60.0 + BAR_WIDTH * 2.0 + 40.0, height);
+    for (i, (name, value)) in rows.iter().enumerate() {
+        draw_bar(g, name, *value, x + 3.0, y + 3.0 + i as f64 * ROW_HEIGHT);
+    }
+}
+
+//从汽车画线到它"看到"的外星人(黄色)和外星人子弹(红色)
+pub fn draw_targets(g: &mut Graphics, sensors: &SensorConfig, world: &World) {
+    let car = world.car().center();
+    let (car_x, car_y) = (car.x as f64, car.y as f64);
+    let (aliens, alien_missiles) = sensors.targets(world);
+    for &(x, y) in &aliens {
+        dotted_line(g, &[255, 220, 0, 200], car_x, car_y, x, y);
+    }
+    for &(x, y) in &alien_missiles {
+        dotted_line(g, &[255, 60, 60, 200], car_x, car_y, x, y);
+    }
+}
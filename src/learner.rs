@@ -0,0 +1,533 @@
+use super::ai::{decide, Action, ActionMode};
+use super::controller::CarController;
+use super::env::{EnvConfig, Observation, SpaceOutEnv};
+use super::fitness::{EpisodeEnd, EpisodeStats};
+use super::pixels::FrameStack;
+use super::random::Random;
+use super::sensors::SensorConfig;
+use super::simulation::World;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+//基于价值的强化学习，作为NEAT的对照：Q表(把网络输入离散化为状态)和带经验回放的小型DQN
+//观测和动作与CarBrain相同：观测为sensors的网络输入，动作是几组固定的网络输出，经过decide转换为动作
+
+pub const LEARNER_NAMES: &[&str] = &["qlearning", "dqn"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LearnerConfig {
+    //qlearning或dqn
+    pub kind: String,
+    //训练的局数
+    pub episodes: usize,
+    //未来奖励的折扣
+    pub gamma: f64,
+    //探索率在epsilon_episodes局内从epsilon_start线性下降到epsilon_end
+    pub epsilon_start: f64,
+    pub epsilon_end: f64,
+    pub epsilon_episodes: usize,
+    //Q表的学习率和每个输入(-1~1)分成的区间数
+    pub alpha: f64,
+    pub bins: usize,
+    //DQN隐藏层的神经元数量和学习率
+    pub hidden: usize,
+    pub learning_rate: f64,
+    //经验回放：缓冲区大小、每次训练的样本数、开始训练前至少收集的经验数、每隔几帧训练一次
+    pub replay_capacity: usize,
+    pub batch_size: usize,
+    pub warmup: usize,
+    pub train_every: usize,
+    //每隔多少帧把网络复制到目标网络
+    pub target_update: usize,
+}
+
+impl Default for LearnerConfig {
+    fn default() -> LearnerConfig {
+        LearnerConfig {
+            kind: "qlearning".to_string(),
+            episodes: 2000,
+            gamma: 0.99,
+            epsilon_start: 1.0,
+            epsilon_end: 0.05,
+            epsilon_episodes: 1000,
+            alpha: 0.1,
+            bins: 10,
+            hidden: 64,
+            learning_rate: 0.001,
+            replay_capacity: 50000,
+            batch_size: 32,
+            warmup: 1000,
+            train_every: 4,
+            target_update: 1000,
+        }
+    }
+}
+
+//可以选择的动作：每种动作模式的几组网络输出
+pub fn discrete_outputs(mode: ActionMode) -> Vec<Vec<f64>> {
+    let mut outputs = vec![];
+    for &fire in &[0.0, 1.0] {
+        match mode {
+            ActionMode::Binary => {
+                outputs.push(vec![1.0, fire]);
+                outputs.push(vec![0.0, fire]);
+            }
+            ActionMode::Idle => {
+                outputs.push(vec![1.0, 0.0, fire]);
+                outputs.push(vec![0.0, 1.0, fire]);
+                outputs.push(vec![0.0, 0.0, fire]);
+            }
+            //目标速度分为5档
+            ActionMode::Analog => {
+                for &speed in &[0.0, 0.25, 0.5, 0.75, 1.0] {
+                    outputs.push(vec![speed, fire]);
+                }
+            }
+        }
+    }
+    outputs
+}
+
+//一帧的经验
+#[derive(Clone, Debug)]
+pub struct Transition {
+    pub observation: Observation,
+    pub action: usize,
+    pub reward: f64,
+    pub next: Observation,
+    //汽车被击中(超时和停滞只是截断，仍然估计之后的价值)
+    pub terminal: bool,
+}
+
+//Q表：每个输入离散化后的状态对应每个动作的价值
+#[derive(Clone, Debug)]
+pub struct QTable {
+    bins: usize,
+    actions: usize,
+    values: HashMap<Vec<u8>, Vec<f64>>,
+}
+
+impl QTable {
+    pub fn new(bins: usize, actions: usize) -> QTable {
+        QTable {
+            bins: bins.clamp(1, 256),
+            actions,
+            values: HashMap::new(),
+        }
+    }
+
+    fn state(&self, observation: &[f64]) -> Vec<u8> {
+        observation
+            .iter()
+            .map(|v| (((v.clamp(-1.0, 1.0) + 1.0) / 2.0 * self.bins as f64) as usize).min(self.bins - 1) as u8)
+            .collect()
+    }
+
+    pub fn q_values(&self, observation: &[f64]) -> Vec<f64> {
+        match self.values.get(&self.state(observation)) {
+            Some(values) => values.clone(),
+            None => vec![0.0; self.actions],
+        }
+    }
+
+    pub fn learn(&mut self, transition: &Transition, config: &LearnerConfig) {
+        let target = transition.reward
+            + if transition.terminal {
+                0.0
+            } else {
+                config.gamma * max_value(&self.q_values(&transition.next))
+            };
+        let actions = self.actions;
+        let values = self
+            .values
+            .entry(self.state(&transition.observation))
+            .or_insert_with(|| vec![0.0; actions]);
+        values[transition.action] += config.alpha * (target - values[transition.action]);
+    }
+
+    //访问过的状态数量
+    pub fn states(&self) -> usize {
+        self.values.len()
+    }
+}
+
+//单隐藏层的全连接网络(ReLU)，输出每个动作的价值
+#[derive(Clone, Debug)]
+struct Mlp {
+    inputs: usize,
+    hidden: usize,
+    outputs: usize,
+    //按行存储：w1[j * inputs + i]、w2[k * hidden + j]
+    w1: Vec<f64>,
+    b1: Vec<f64>,
+    w2: Vec<f64>,
+    b2: Vec<f64>,
+}
+
+impl Mlp {
+    fn new(inputs: usize, hidden: usize, outputs: usize, rng: &mut Random) -> Mlp {
+        let mut weights = |count: usize, fan_in: usize| -> Vec<f64> {
+            let scale = (1.0 / fan_in.max(1) as f64).sqrt();
+            (0..count).map(|_| (rng.next_f64() * 2.0 - 1.0) * scale).collect()
+        };
+        Mlp {
+            inputs,
+            hidden,
+            outputs,
+            w1: weights(hidden * inputs, inputs),
+            b1: vec![0.0; hidden],
+            w2: weights(outputs * hidden, hidden),
+            b2: vec![0.0; outputs],
+        }
+    }
+
+    //返回隐藏层和输出层
+    fn forward(&self, x: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        let hidden: Vec<f64> = (0..self.hidden)
+            .map(|j| {
+                let row = &self.w1[j * self.inputs..(j + 1) * self.inputs];
+                let sum: f64 = row.iter().zip(x).map(|(w, x)| w * x).sum::<f64>() + self.b1[j];
+                sum.max(0.0)
+            })
+            .collect();
+        let output = (0..self.outputs)
+            .map(|k| {
+                let row = &self.w2[k * self.hidden..(k + 1) * self.hidden];
+                row.iter().zip(&hidden).map(|(w, h)| w * h).sum::<f64>() + self.b2[k]
+            })
+            .collect();
+        (hidden, output)
+    }
+
+    //一步梯度下降，使输出action接近target(误差截断到-1~1，相当于Huber损失)
+    fn train(&mut self, x: &[f64], action: usize, target: f64, learning_rate: f64) {
+        let (hidden, output) = self.forward(x);
+        let error = (output[action] - target).clamp(-1.0, 1.0);
+        for (j, h) in hidden.iter().enumerate() {
+            let index = action * self.hidden + j;
+            if *h > 0.0 {
+                let delta = error * self.w2[index];
+                let row = &mut self.w1[j * self.inputs..(j + 1) * self.inputs];
+                for (w, x) in row.iter_mut().zip(x) {
+                    *w -= learning_rate * delta * x;
+                }
+                self.b1[j] -= learning_rate * delta;
+            }
+            self.w2[index] -= learning_rate * error * h;
+        }
+        self.b2[action] -= learning_rate * error;
+    }
+}
+
+//DQN：在线网络从经验回放中学习，目标网络定期同步，用于计算目标价值
+pub struct Dqn {
+    net: Mlp,
+    target: Mlp,
+    replay: VecDeque<Transition>,
+    steps: usize,
+}
+
+impl Dqn {
+    pub fn new(inputs: usize, actions: usize, config: &LearnerConfig, rng: &mut Random) -> Dqn {
+        let net = Mlp::new(inputs, config.hidden.max(1), actions, rng);
+        Dqn {
+            target: net.clone(),
+            net,
+            replay: VecDeque::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn q_values(&self, observation: &[f64]) -> Vec<f64> {
+        self.net.forward(observation).1
+    }
+
+    pub fn learn(&mut self, transition: Transition, config: &LearnerConfig, rng: &mut Random) {
+        if self.replay.len() >= config.replay_capacity.max(1) {
+            self.replay.pop_front();
+        }
+        self.replay.push_back(transition);
+        self.steps += 1;
+
+        if self.replay.len() >= config.warmup.max(config.batch_size) && self.steps.is_multiple_of(config.train_every.max(1)) {
+            for _ in 0..config.batch_size {
+                let sample = &self.replay[rng.rand_int(0, self.replay.len() as i32) as usize];
+                let target = sample.reward
+                    + if sample.terminal {
+                        0.0
+                    } else {
+                        config.gamma * max_value(&self.target.forward(&sample.next).1)
+                    };
+                self.net.train(&sample.observation, sample.action, target, config.learning_rate);
+            }
+        }
+        if self.steps.is_multiple_of(config.target_update.max(1)) {
+            self.target = self.net.clone();
+        }
+    }
+}
+
+pub enum Learner {
+    Table(QTable),
+    Dqn(Box<Dqn>),
+}
+
+impl Learner {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Learner::Table(_) => "qlearning",
+            Learner::Dqn(_) => "dqn",
+        }
+    }
+
+    pub fn q_values(&self, observation: &[f64]) -> Vec<f64> {
+        match self {
+            Learner::Table(table) => table.q_values(observation),
+            Learner::Dqn(dqn) => dqn.q_values(observation),
+        }
+    }
+
+    //只复制价值函数，不复制经验回放
+    fn values(&self) -> Values {
+        match self {
+            Learner::Table(table) => Values::Table(table.clone()),
+            Learner::Dqn(dqn) => Values::Net(dqn.net.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Values {
+    Table(QTable),
+    Net(Mlp),
+}
+
+impl Values {
+    fn q_values(&self, observation: &[f64]) -> Vec<f64> {
+        match self {
+            Values::Table(table) => table.q_values(observation),
+            Values::Net(net) => net.forward(observation).1,
+        }
+    }
+}
+
+fn max_value(values: &[f64]) -> f64 {
+    values.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+}
+
+//价值最大的动作
+fn best_action(values: &[f64]) -> usize {
+    let mut best = 0;
+    for (i, &value) in values.iter().enumerate() {
+        if value > values[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+//在强化学习环境中训练学习器
+pub struct Agent {
+    config: LearnerConfig,
+    env: SpaceOutEnv,
+    outputs: Vec<Vec<f64>>,
+    learner: Learner,
+    rng: Random,
+    episode: usize,
+}
+
+impl Agent {
+    //未知的学习器返回None
+    pub fn new(config: LearnerConfig, env: EnvConfig, seed: u64) -> Option<Agent> {
+        let mut rng = Random::new(seed);
+        let outputs = discrete_outputs(env.sensors.actions);
+        //网络的输入数量和环境的观测相同(使用像素观测时不是传感器的输入数量)
+        let env = SpaceOutEnv::new(env);
+        let learner = match config.kind.as_str() {
+            "qlearning" => Learner::Table(QTable::new(config.bins, outputs.len())),
+            "dqn" => Learner::Dqn(Box::new(Dqn::new(env.observation_size(), outputs.len(), &config, &mut rng))),
+            _ => return None,
+        };
+        Some(Agent {
+            config,
+            env,
+            outputs,
+            learner,
+            rng,
+            episode: 0,
+        })
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        let progress = (self.episode as f64 / self.config.epsilon_episodes.max(1) as f64).min(1.0);
+        self.config.epsilon_start + (self.config.epsilon_end - self.config.epsilon_start) * progress
+    }
+
+    //按探索率随机选择动作，否则选择价值最大的动作
+    fn choose(&mut self, observation: &[f64]) -> usize {
+        if self.rng.next_f64() < self.epsilon() {
+            self.rng.rand_int(0, self.outputs.len() as i32) as usize
+        } else {
+            best_action(&self.learner.q_values(observation))
+        }
+    }
+
+    //在种子决定的游戏世界中训练一局，返回这一局的统计和总奖励
+    pub fn train_episode(&mut self, seed: u64) -> (EpisodeStats, f64) {
+        let mut observation = self.env.reset(seed);
+        let mut total = 0.0;
+        while !self.env.done() {
+            let action = self.choose(&observation);
+            let (next, reward, done, info) = self.env.step(self.env.decide(&self.outputs[action]));
+            total += reward;
+            let transition = Transition {
+                observation,
+                action,
+                reward,
+                next: next.clone(),
+                terminal: done && info.end == Some(EpisodeEnd::CarHit),
+            };
+            match &mut self.learner {
+                Learner::Table(table) => table.learn(&transition, &self.config),
+                Learner::Dqn(dqn) => dqn.learn(transition, &self.config, &mut self.rng),
+            }
+            observation = next;
+        }
+        self.episode += 1;
+        (*self.env.stats(), total)
+    }
+
+    pub fn episode(&self) -> usize {
+        self.episode
+    }
+
+    pub fn learner(&self) -> &Learner {
+        &self.learner
+    }
+
+    //不再探索的驾驶者，用于和其它驾驶者在相同的种子上比较
+    pub fn policy(&self) -> GreedyController {
+        GreedyController {
+            name: self.learner.name(),
+            values: self.learner.values(),
+            sensors: self.env.config().sensors.clone(),
+            frames: self.env.config().pixels.clone().map(FrameStack::new),
+            outputs: self.outputs.clone(),
+        }
+    }
+}
+
+//总是选择价值最大的动作
+#[derive(Clone)]
+pub struct GreedyController {
+    name: &'static str,
+    values: Values,
+    sensors: SensorConfig,
+    //使用像素观测训练时，和环境一样叠加最近几帧的画面
+    frames: Option<FrameStack>,
+    outputs: Vec<Vec<f64>>,
+}
+
+impl CarController for GreedyController {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn act(&mut self, world: &World) -> Action {
+        let observation = match self.frames.as_mut() {
+            //和SpaceOutEnv相同：第一帧填满，之后每一帧加入最新的画面
+            Some(frames) => {
+                if frames.latest().is_none() {
+                    frames.reset(world);
+                } else {
+                    frames.push(world);
+                }
+                frames.observation()
+            }
+            None => self.sensors.observe(world),
+        };
+        let action = best_action(&self.values.q_values(&observation));
+        decide(&self.outputs[action], self.sensors.actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::PixelConfig;
+
+    //两个状态、两个动作的赌博机：状态A中动作0得到1分，状态B中动作1得到1分，其它为0分，每一步都结束
+    fn bandit(rng: &mut Random) -> Transition {
+        let state = rng.rand_int(0, 2) as usize;
+        let action = rng.rand_int(0, 2) as usize;
+        let observation = if state == 0 { vec![-0.5, 0.5] } else { vec![0.5, -0.5] };
+        Transition {
+            next: observation.clone(),
+            observation,
+            action,
+            reward: if state == action { 1.0 } else { 0.0 },
+            terminal: true,
+        }
+    }
+
+    fn assert_learned(q_values: impl Fn(&[f64]) -> Vec<f64>) {
+        let a = q_values(&[-0.5, 0.5]);
+        let b = q_values(&[0.5, -0.5]);
+        assert_eq!((best_action(&a), best_action(&b)), (0, 1));
+        assert!((a[0] - 1.0).abs() < 0.1 && a[1].abs() < 0.1, "{:?}", a);
+        assert!((b[1] - 1.0).abs() < 0.1 && b[0].abs() < 0.1, "{:?}", b);
+    }
+
+    #[test]
+    fn q_table_learns_a_two_state_bandit() {
+        let config = LearnerConfig::default();
+        let mut rng = Random::new(1);
+        let mut table = QTable::new(config.bins, 2);
+        for _ in 0..1000 {
+            table.learn(&bandit(&mut rng), &config);
+        }
+        assert_eq!(table.states(), 2);
+        assert_learned(|observation| table.q_values(observation));
+    }
+
+    #[test]
+    fn dqn_learns_a_two_state_bandit() {
+        let config = LearnerConfig {
+            hidden: 8,
+            learning_rate: 0.05,
+            replay_capacity: 100,
+            batch_size: 8,
+            warmup: 20,
+            train_every: 1,
+            target_update: 50,
+            ..LearnerConfig::default()
+        };
+        let mut rng = Random::new(1);
+        let mut dqn = Dqn::new(2, 2, &config, &mut rng);
+        for _ in 0..3000 {
+            let transition = bandit(&mut rng);
+            dqn.learn(transition, &config, &mut rng);
+        }
+        //经验回放只保留最近的经验
+        assert_eq!(dqn.replay.len(), config.replay_capacity);
+        assert_learned(|observation| dqn.q_values(observation));
+    }
+
+    #[test]
+    fn pixel_policy_sees_the_same_observation_as_the_env() {
+        let env = EnvConfig {
+            pixels: Some(PixelConfig::default()),
+            ..EnvConfig::default()
+        };
+        let config = LearnerConfig {
+            kind: "dqn".to_string(),
+            ..LearnerConfig::default()
+        };
+        let mut policy = Agent::new(config, env.clone(), 1).unwrap().policy();
+        let mut world = World::with_config(true, 1, env.world.clone());
+        world.update();
+        policy.act(&world);
+        let observation = SpaceOutEnv::new(env).reset(1);
+        assert_eq!(policy.frames.as_ref().unwrap().observation(), observation);
+    }
+}
@@ -0,0 +1,41 @@
+//游戏模拟和训练代码，窗口程序(main.rs)和命令行训练程序(bin/train.rs)共用
+
+//输出日志，外部驾驶者通过标准输入输出通信时输出到标准错误(见logging.rs)，标准输出只有JSON
+//需要在mod之前定义，各个模块才能使用
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::logging::to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub mod ai;
+pub mod alien_sprite;
+pub mod arena;
+pub mod background;
+pub mod config;
+pub mod controller;
+pub mod curriculum;
+pub mod env;
+pub mod fitness;
+pub mod imitation;
+pub mod inspector;
+pub mod learner;
+pub mod logging;
+pub mod mutation;
+pub mod netdraw;
+pub mod novelty;
+pub mod options;
+pub mod persist;
+pub mod pixels;
+pub mod random;
+pub mod remote;
+pub mod sensors;
+pub mod simulation;
+pub mod stagnation;
+pub mod stats;
+pub mod training;
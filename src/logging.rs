@@ -0,0 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+//log!的输出位置：默认为标准输出，外部驾驶者通过标准输入输出通信时改为标准错误(见remote.rs)
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn to_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
+
+pub fn set_to_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
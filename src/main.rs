@@ -1,16 +1,21 @@
 use mengine::*;
-mod ai;
-mod alien_sprite;
-mod background;
-use background::StarryBackground;
 use engine::GameEngine;
-use engine::{Resource, Sprite, BA_BOUNCE, BA_DIE, BA_WRAP};
+use engine::{Resource, Sprite};
+use spaceout::ai::CarBrain;
+use spaceout::background::StarryBackground;
+use spaceout::env::RewardConfig;
+use spaceout::imitation::Recorder;
+use spaceout::controller::{CarController, HumanController, HumanInput, NetController, ScriptedController, DRIVER_NAMES};
+use spaceout::options::Options;
+use spaceout::persist::SavedNet;
+use spaceout::random::Random;
+use spaceout::remote::{Endpoint, RemoteController};
+use spaceout::simulation::{Alien, GameEvent, Simulation, SpriteKind, CLIENT_HEIGHT, CLIENT_WIDTH};
+use spaceout::{config, inspector, log, logging, netdraw, training};
 use std::cell::RefCell;
-use std::cmp;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::rc::Rc;
-use ai::{Turn, CarBrain};
-use math2d::Vector2f;
 
 pub const ASSETS_SPLASH_BITMAP: &str = "Splash.png";
 pub const ASSETS_DESERT_BITMAP: &str = "Desert.png";
@@ -35,7 +40,7 @@ pub const ASSETS_SM_EXPLODE_SOUND: &str = "SmExplode.ogg";
 pub const ASSETS_MISSILE_SOUND: &str = "Missile.ogg";
 pub const PATH_BACKGROUND_MUSIC: &str = "Music.mp3";
 
-const RESOURCES: &'static [(&'static str, AssetsType); 20] = &[
+const RESOURCES: &[(&str, AssetsType); 20] = &[
     (ASSETS_SPLASH_BITMAP, AssetsType::Image),
     (ASSETS_DESERT_BITMAP, AssetsType::Image),
     (ASSETS_CAR_BITMAP, AssetsType::Image),
@@ -85,188 +90,284 @@ pub struct Stage {
 
 //触摸延迟
 pub const DRIVE_THRESHOLD: i32 = 3;
-pub const CLIENT_WIDTH: f64 = 600.0;
-pub const CLIENT_HEIGHT: f64 = 450.0;
 
-//SpaceOut游戏主结构体
+//SpaceOut游戏主结构体：负责绘制、声音和输入，游戏规则由Simulation处理
 pub struct SpaceOut {
     background: StarryBackground,
     fire_input_delay: i32,
     _last_touch: Option<Point>,
     _drive_left: i32,
     _drive_right: i32,
+    //爆炸效果精灵
     sprites: Vec<Sprite>,
-    car_sprite_id: f64,
-    num_lives: i32,
-    score: i32,
-    demo: bool,
-    difficulty: Rc<RefCell<i32>>,
+    simulation: Simulation,
+    //人类驾驶者的键盘和鼠标状态
+    human_input: Rc<RefCell<HumanInput>>,
+    //network驾驶者使用的网络文件
+    network_path: Option<PathBuf>,
+    //人类驾驶时记录示范数据的文件
+    record_path: Option<PathBuf>,
+    //external驾驶者的连接方式和奖励
+    external: Option<Endpoint>,
+    reward: RewardConfig,
+    //当前驾驶者在DRIVER_NAMES中的序号
+    driver: usize,
     resources: HashMap<String, Assets>,
     stage: Option<Stage>,
-    game_over: bool,
     game_over_delay: i32,
-    brain: CarBrain,
     fast_mode: bool,
+    //快速模式的更新频率
+    fast_mode_ups: u64,
     next_print_time: f64,
-    car_missile_count: i32,//最多10发导弹
+    //演示模式下显示最好的网络结构，缓存最好的网络和它所在的代数
+    show_net: bool,
+    best_net: Option<(i32, SavedNet)>,
+    //显示网络激活值和汽车看到的目标
+    show_inspector: bool,
 }
 
 impl SpaceOut {
     //新游戏
-    fn new_game(&mut self) {
+    fn new_game(&mut self, demo: bool) {
         //清除所有精灵
         self.clean_up_sprites();
         //初始化游戏变量
         self.fire_input_delay = 0;
-        self.score = 0;
-        self.num_lives = 3;
-        self.difficulty = Rc::new(RefCell::new(80));
-        self.game_over = false;
-        let stage = self.stage.as_mut().unwrap();
-
-        //创建汽车
-        let mut car_sprite = Sprite::with_bounds_action(
-            String::from("car"),
-            Resource::Static(stage.img_car.clone()),
-            Rect::new(40.0, 0.0, CLIENT_WIDTH-40.0, CLIENT_HEIGHT),
-            BA_WRAP,
-        );
-        self.car_sprite_id = car_sprite.id();
-        car_sprite.set_position(300.0, 405.0);
-
-        self.add_sprite(car_sprite);
+        *self.human_input.borrow_mut() = HumanInput::default();
+        self.simulation.new_game(demo);
 
-        if self.demo {
-            //添加一些外星人
-            for _ in 0..6 {
-                self.add_alien();
-            }
-        } else {
+        if !demo {
             play_music(PATH_BACKGROUND_MUSIC, true);
         }
     }
 
-    //添加外星人
-    fn add_alien(&mut self) {
-        //创建一个随机的外星人精灵
-        let bounds = Rect::new(0.0, 0.0, CLIENT_WIDTH, 410.0);
-        let ext = {
-            let stage = self.stage.as_ref().unwrap();
-            alien_sprite::AlienSprite {
-                difficulty: self.difficulty.clone(),
-                img_jmissile: stage.img_jmissile.clone(),
-                img_tmissile: stage.img_tmissile.clone(),
-                img_bmissile: stage.img_bmissile.clone(),
+    //在指定位置添加爆炸精灵
+    fn add_explosion(&mut self, large: bool, x: f64, y: f64) {
+        let stage = self.stage.as_ref().unwrap();
+        let (name, image, frames) = if large {
+            let mut frames = vec![];
+            for y in (0..272).step_by(34) {
+                frames.push([0., y as f64, 33., 34.]);
             }
+            ("lg_explosion", stage.img_lg_explosion.clone(), frames)
+        } else {
+            let mut frames = vec![];
+            for y in (0..136).step_by(17) {
+                frames.push([0., y as f64, 17., 17.]);
+            }
+            ("sm_explosion", stage.img_sm_explosion.clone(), frames)
         };
-        self.add_sprite(match 2 {
-            1 => {
-                // Blobbo
-                let mut frames = vec![];
-                for y in (0..272).step_by(34) {
-                    frames.push([0., y as f64, 32., 34.]);
-                }
-                let mut anim = Animation::active(
-                    self.stage.as_ref().unwrap().img_blobbo.clone(),
-                    frames,
-                    25.0,
-                );
-                anim.set_repeat(true);
+        let anim = Animation::active(image, frames, 25.0);
 
-                let mut sprite = Sprite::with_bounds_action(
-                    String::from("blobbo"),
-                    Resource::Animation(anim),
-                    bounds,
-                    BA_BOUNCE,
-                );
-                sprite.set_position(
-                    match rand_int(0, 2) {
-                        0 => 0.0,
-                        _ => 600.0,
+        let mut sprite = Sprite::from_bitmap(
+            String::from(name),
+            Resource::Animation(anim),
+            Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
+        );
+        sprite.set_position(x, y);
+        self.add_sprite(sprite);
+    }
+
+    //选择驾驶者，网络文件加载失败时返回false
+    fn select_driver(&mut self, index: usize) -> bool {
+        let driver: Option<Box<dyn CarController>> = match DRIVER_NAMES[index] {
+            "human" => {
+                let human = HumanController::new(self.human_input.clone());
+                match self.record_path.as_ref() {
+                    //记录人类驾驶的示范数据
+                    Some(path) => match Recorder::open(path, self.simulation.brain().sensors().clone(), human) {
+                        Ok(recorder) => Some(Box::new(recorder)),
+                        Err(err) => {
+                            log!("打开示范数据文件失败:{:?} {:?}", path, err);
+                            Some(Box::new(HumanController::new(self.human_input.clone())))
+                        }
                     },
-                    rand_int(0, 370) as f64,
-                );
-                sprite.ext(ext);
-                sprite.set_velocity(rand_int(0, 7) as f64 - 2.0, rand_int(0, 7) as f64 - 2.0);
-                sprite
+                    None => Some(Box::new(human)),
+                }
             }
-            2 => {
-                // Jelly
-                let mut frames = vec![];
-                for y in (0..264).step_by(33) {
-                    frames.push([0., y as f64, 33., 33.]);
+            "network" => {
+                let path = match self.network_path.as_ref() {
+                    Some(path) => path,
+                    None => return false,
+                };
+                match NetController::load(path, self.simulation.brain().sensors().clone()) {
+                    Ok(net) => Some(Box::new(net)),
+                    Err(err) => {
+                        log!("加载网络失败:{:?} {:?}", path, err);
+                        return false;
+                    }
                 }
-                let mut anim =
-                    Animation::active(self.stage.as_ref().unwrap().img_jelly.clone(), frames, 25.0);
-                anim.set_repeat(true);
-
-                let mut sprite = Sprite::with_bounds_action(
-                    String::from("jelly"),
-                    Resource::Animation(anim),
-                    bounds,
-                    BA_BOUNCE,
-                );
-                sprite.set_position(
-                    rand_int(0, CLIENT_WIDTH as i32) as f64,
-                    rand_int(0, 370) as f64,
-                );
-                sprite.set_velocity(rand_int(0, 5) as f64 - 2., rand_int(0, 5) as f64 + 3.);
-                sprite.ext(ext);
-                sprite
             }
-            _ => {
-                // Timmy
-                let mut frames = vec![];
-                for y in (0..136).step_by(17) {
-                    frames.push([0., y as f64, 33., 17.]);
+            "scripted" => Some(Box::new(ScriptedController)),
+            "external" => {
+                let endpoint = match self.external {
+                    Some(endpoint) => endpoint,
+                    None => return false,
+                };
+                let sensors = self.simulation.brain().sensors().clone();
+                match RemoteController::connect(endpoint, sensors, self.reward.clone()) {
+                    Ok(remote) => Some(Box::new(remote)),
+                    Err(err) => {
+                        log!("连接外部驾驶者失败:{:?} {:?}", endpoint, err);
+                        return false;
+                    }
                 }
+            }
+            _ => None,
+        };
+        self.simulation.set_driver(driver);
+        self.driver = index;
+        true
+    }
 
-                let mut anim =
-                    Animation::active(self.stage.as_ref().unwrap().img_timmy.clone(), frames, 25.0);
-                anim.set_repeat(true);
+    //切换到下一个可用的驾驶者
+    fn next_driver(&mut self) {
+        for i in 1..DRIVER_NAMES.len() {
+            if self.select_driver((self.driver + i) % DRIVER_NAMES.len()) {
+                break;
+            }
+        }
+    }
 
-                let mut sprite = Sprite::with_bounds_action(
-                    String::from("timmy"),
-                    Resource::Animation(anim),
-                    bounds,
-                    BA_WRAP,
-                );
-                sprite.set_position(
-                    rand_int(0, CLIENT_WIDTH as i32) as f64,
-                    rand_int(0, 370) as f64,
-                );
-                sprite.set_velocity(rand_int(0, 7) as f64 + 3., 0.);
-                sprite.ext(ext);
-                sprite
+    //播放事件对应的声音和爆炸动画
+    fn handle_event(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::MissileFired { .. } => {
+                //播放导弹发射声音(演示模式下只播放人类玩家的)
+                if self.simulation.driver_name() == "human" || !self.simulation.world().demo() {
+                    mengine::play_sound(&self.stage.as_ref().unwrap().sound_missile);
+                }
             }
-        });
+            GameEvent::MissileExploded { x, y } => {
+                //播放小的爆炸声音
+                if !self.simulation.world().demo() {
+                    mengine::play_sound(&self.stage.as_ref().unwrap().sound_sm_explode);
+                }
+                self.add_explosion(false, x, y);
+            }
+            GameEvent::AlienKilled { x, y, .. } => {
+                //播放小的爆炸声音
+                mengine::play_sound(&self.stage.as_ref().unwrap().sound_sm_explode);
+                self.add_explosion(true, x, y);
+            }
+            GameEvent::CarHit { x, y, .. } => {
+                //播放大的爆炸声音(整个种群同时训练时汽车太多，不播放)
+                if !self.simulation.population_mode() {
+                    mengine::play_sound(&self.stage.as_ref().unwrap().sound_lg_explode);
+                }
+                self.add_explosion(true, x, y);
+            }
+            GameEvent::NearMiss { .. } => (),
+            GameEvent::GameOver => {
+                //播放游戏结束声音
+                mengine::play_sound(&self.stage.as_ref().unwrap().sound_gameover);
+                self.game_over_delay = 150;
+            }
+        }
+    }
+
+    //绘制模拟中的精灵
+    fn draw_bodies(&self, g: &mut Graphics) {
+        let stage = self.stage.as_ref().unwrap();
+        let population_mode = self.simulation.population_mode();
+        for body in self.simulation.world().bodies() {
+            let pos = body.position();
+            if population_mode && body.kind() == SpriteKind::Car {
+                //整个种群同时训练时用半透明的方块表示每一辆汽车
+                g.fill_rect(&[0, 200, 255, 70], pos.left, pos.top, body.width(), body.height());
+                continue;
+            }
+            let (image, animated) = match body.kind() {
+                SpriteKind::Car => (&stage.img_car, false),
+                SpriteKind::Missile => (&stage.img_missile, false),
+                SpriteKind::Alien(Alien::Blobbo) => (&stage.img_blobbo, true),
+                SpriteKind::Alien(Alien::Jelly) => (&stage.img_jelly, true),
+                SpriteKind::Alien(Alien::Timmy) => (&stage.img_timmy, true),
+                SpriteKind::AlienMissile(Alien::Blobbo) => (&stage.img_bmissile, false),
+                SpriteKind::AlienMissile(Alien::Jelly) => (&stage.img_jmissile, false),
+                SpriteKind::AlienMissile(Alien::Timmy) => (&stage.img_tmissile, false),
+            };
+            let (width, height) = (body.width(), body.height());
+            //外星人动画共8帧，纵向排列
+            let frame = if animated {
+                Some([0.0, (body.age() % 8) as f64 * height, width, height])
+            } else {
+                None
+            };
+            g.draw_image(None, image, frame, Some([pos.left, pos.top, width, height]));
+        }
     }
 }
 
 impl State for SpaceOut {
     fn new(window: &mut Window) -> Self {
         window.load_assets(RESOURCES.to_vec());
-        SpaceOut {
-            background: StarryBackground::default(CLIENT_WIDTH, CLIENT_HEIGHT),
+        let options = Options::from_args();
+        let config = config::load_or_exit(&options);
+        //没有指定种子时使用当前时间，并打印出来以便重现
+        let seed = config.seed.unwrap_or_else(training::default_seed);
+        log!("随机数种子:{}", seed);
+        let mut brain = config.create_brain();
+        if let Some(path) = options.resume.as_ref() {
+            //从检查点恢复训练
+            match CarBrain::resume(path) {
+                Ok(resumed) => brain = resumed,
+                Err(err) => log!("加载检查点失败:{:?} {:?}", path, err),
+            }
+        }
+        config.configure(&mut brain, &options, seed);
+        if let Some(path) = options.load_brain.as_ref() {
+            //使用之前训练好的网络进行控制
+            match brain.load_best(path) {
+                Ok(()) => brain.use_best(true),
+                Err(err) => log!("加载网络失败:{:?} {:?}", path, err),
+            }
+        }
+        let simulation = Simulation::new(brain);
+        let mut spaceout = SpaceOut {
+            //背景使用单独的随机数序列，不影响游戏模拟
+            background: StarryBackground::default(CLIENT_WIDTH, CLIENT_HEIGHT, Random::new(!seed)),
             fire_input_delay: 0,
             _last_touch: None,
             _drive_left: 0,
             _drive_right: 0,
             sprites: vec![],
-            car_sprite_id: 0.0,
-            num_lives: 3,
-            score: 0,
-            demo: true,
+            simulation,
+            human_input: Rc::new(RefCell::new(HumanInput::default())),
+            network_path: options.load_brain.clone(),
+            record_path: options.record.clone(),
+            external: options.external.as_ref().and_then(|name| {
+                let endpoint = Endpoint::parse(name);
+                if endpoint.is_none() {
+                    log!("--external 需要stdio或tcp:端口");
+                }
+                endpoint
+            }),
+            reward: config.reward.clone(),
+            driver: 0,
             resources: HashMap::new(),
             stage: None,
-            difficulty: Rc::new(RefCell::new(80)),
-            game_over: false,
             game_over_delay: 0,
-            brain: CarBrain::new(),
             fast_mode: false,
+            fast_mode_ups: config.fast_mode_ups,
             next_print_time: current_timestamp()+2000.0,
-            car_missile_count: 0,
+            show_net: true,
+            best_net: None,
+            show_inspector: false,
+        };
+        //指定--external时默认由外部程序驾驶
+        let driver = options.driver.clone().or_else(|| options.external.as_ref().map(|_| "external".to_string()));
+        if let Some(name) = driver.as_ref() {
+            match DRIVER_NAMES.iter().position(|driver| driver == name) {
+                Some(index) => {
+                    if !spaceout.select_driver(index) {
+                        log!("无法使用驾驶者:{}", name);
+                    }
+                }
+                None => log!("未知的驾驶者:{} 可选:{:?}", name, DRIVER_NAMES),
+            }
         }
+        spaceout
     }
 
     fn on_assets_load(
@@ -405,12 +506,12 @@ impl State for SpaceOut {
                             .unwrap(),
                     });
 
-                    self.new_game();
+                    self.new_game(true);
                 }
             }
             Err(err) => alert(
                 "温馨提示",
-                &format!("资源文件加载失败:{:?} {:?}", path, err).as_str(),
+                format!("资源文件加载失败:{:?} {:?}", path, err).as_str(),
             ),
         }
     }
@@ -420,64 +521,57 @@ impl State for SpaceOut {
             return;
         }
         match event {
+            Event::KeyDown(key) => match key.to_lowercase().as_str() {
+                "left" | "arrowleft" => self.human_input.borrow_mut().left = true,
+                "right" | "arrowright" => self.human_input.borrow_mut().right = true,
+                "up" | "arrowup" => self.human_input.borrow_mut().fire = true,
+                _ => (),
+            },
             Event::KeyUp(key) => {
+                match key.to_lowercase().as_str() {
+                    "left" | "arrowleft" => self.human_input.borrow_mut().left = false,
+                    "right" | "arrowright" => self.human_input.borrow_mut().right = false,
+                    //切换驾驶者
+                    "d" => self.next_driver(),
+                    //显示/隐藏网络结构
+                    "n" => self.show_net = !self.show_net,
+                    //显示/隐藏网络激活值
+                    "i" => self.show_inspector = !self.show_inspector,
+                    //整个种群同时训练/逐个网络训练(快速模式下无效)
+                    "p" if !self.fast_mode => {
+                        let enabled = !self.simulation.population_mode();
+                        self.simulation.set_population_mode(enabled);
+                    }
+                    _ => (),
+                }
                 if key.to_lowercase() == "enter" {
                     //如果游戏没有开始，启动游戏
-                    // if self.demo || self.game_over {
-                    //     self.demo = false;
-                    //     self.new_game();
+                    // if self.simulation.world().demo() || self.simulation.world().game_over() {
+                    //     self.new_game(false);
                     //     return;
                     // }
                 }
-                if key.to_lowercase() == "space"{
-                    if self.demo{
-                        self.fast_mode = !self.fast_mode;
-                        if self.fast_mode{
-                            self.brain.use_best(false);
-                            window.set_update_rate(90000);
-                        }else{
-                            //选择最好的网络进行控制
-                            self.brain.use_best(true);
-                            window.set_update_rate(30);
-                        }
+                if key.to_lowercase() == "space" && self.simulation.world().demo(){
+                    self.fast_mode = !self.fast_mode;
+                    //快速模式下每一帧在每个CPU核心上评估一个网络
+                    if self.fast_mode{
+                        window.set_update_rate(self.fast_mode_ups);
+                        self.simulation.set_population_mode(false);
+                        self.simulation.brain_mut().use_best(false);
+                    }else{
+                        window.set_update_rate(30);
+                        //选择最好的网络进行控制
+                        self.simulation.brain_mut().use_best(true);
                     }
                 }
             }
             Event::Click(_x, _y) => {
-                return;
-                //如果游戏没有开始，启动游戏
-                if self.demo || self.game_over {
-                    if self.game_over_delay == 0 {
-                        self.demo = false;
-                        self.new_game();
-                    }
-                    return;
-                }
-
-                let car_left_pos = self.get_sprite(self.car_sprite_id).unwrap().position().left;
-                //创建一个新的导弹精灵
-                let mut sprite = Sprite::with_bounds_action(
-                    String::from("missile"),
-                    Resource::Static(self.stage.as_ref().unwrap().img_missile.clone()),
-                    Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
-                    BA_DIE,
-                );
-                sprite.set_position(car_left_pos + 15.0, 400.0);
-                sprite.set_velocity(0.0, -7.0);
-                self.add_sprite(sprite);
-
-                //播放导弹发射声音
-                mengine::play_sound(&self.stage.as_ref().unwrap().sound_missile);
+                //人类驾驶时发射导弹
+                self.human_input.borrow_mut().fire = true;
             }
             Event::MouseMove(x, _y) => {
-                if self.demo {
-                    return;
-                }
-                //直接拖动控制
-                let car_sprite = self.get_sprite(self.car_sprite_id).unwrap();
-                if x >= 0.0 && x + car_sprite.width() <= CLIENT_WIDTH {
-                    car_sprite.set_position(x, car_sprite.position().top);
-                }
+                //人类驾驶时汽车向鼠标位置行驶
+                self.human_input.borrow_mut().target_x = Some(x);
 
                 //移动汽车
                 // match self.last_touch{
@@ -485,26 +579,6 @@ impl State for SpaceOut {
                 //         //判断滑动方向
                 //         if touch_point.x > x{ self.drive_left = cmp::min(self.drive_left+1, DRIVE_THRESHOLD+1);  }//向左
                 //         if touch_point.x < x{ self.drive_right = cmp::min(self.drive_right+1, DRIVE_THRESHOLD+1); }//向右
-
-                //         //判断是否执行
-                //         let satisfied = self.drive_left>DRIVE_THRESHOLD || self.drive_right>DRIVE_THRESHOLD;
-
-                //         let mut car_sprite = self.engine.get_sprite(self.car_sprite_id).unwrap();
-                //         let (vx, vy) = (car_sprite.velocity().x, car_sprite.velocity().y);
-                //         if self.drive_left > DRIVE_THRESHOLD {
-                //             // Move Left
-                //             car_sprite.set_velocity(cmp::max(vx-3, -6), vy);
-                //         }else if self.drive_right > DRIVE_THRESHOLD {
-                //             // Move Right
-                //             car_sprite.set_velocity(cmp::min(vx+3, 6), vy);
-                //         }
-                //         if satisfied {
-                //             self.drive_left = 0;
-                //             self.drive_right = 0;
-                //         }
-                //     }
-                //     _ => self.last_touch = Some(Point{x:x, y:y})
-                // }
             }
             _ => (),
         }
@@ -562,11 +636,21 @@ impl State for SpaceOut {
         }
 
         //绘制精灵
+        self.draw_bodies(g);
         self.draw_sprites(g);
 
+        if self.show_inspector {
+            let brain = self.simulation.brain();
+            inspector::draw_targets(g, brain.sensors(), self.simulation.world());
+            //只有正在训练的网络逐个驾驶时才有激活值
+            if self.simulation.driver_name() == "brain" && !self.simulation.population_mode() {
+                inspector::draw_activations(g, brain.sensors(), brain.activations(), 5.0, 75.0);
+            }
+        }
+
         let stage = self.stage.as_ref().unwrap();
 
-        if self.demo {
+        if self.simulation.world().demo() {
             //绘制闪屏图片
             g.draw_image(
                 None,
@@ -590,13 +674,36 @@ impl State for SpaceOut {
             );
             g.draw_text("左滑->倒车", 260.0, 330.0, &[255, 255, 255, 255], 13);
             g.draw_text("右滑->前进", 260.0, 360.0, &[255, 255, 255, 255], 13);
+            g.draw_text(&format!("驾驶者:{} (D键切换)", self.simulation.driver_name()), 230.0, 390.0, &[255, 255, 255, 255], 13);
 
             //显示AI信息
-            g.draw_text(&format!("Generation:{}, Brain:{} 最高分:{}", self.brain.current_generation(), self.brain.current_brain(), self.brain.max_score()), 5.0, 5.0, &[255, 255, 255, 255], 13);
+            let brain = self.simulation.brain();
+            g.draw_text(&format!("Generation:{}, Brain:{} 最高分:{} ({} {})", brain.current_generation(), brain.current_brain(), brain.max_score(), brain.fitness().name(), brain.aggregation().name()), 5.0, 5.0, &[255, 255, 255, 255], 13);
+            if let Some(stats) = brain.last_stats() {
+                g.draw_text(&format!("上一代 平均:{:.0} 中位数:{:.0} 物种:{}", stats.mean, stats.median, stats.species), 5.0, 22.0, &[255, 255, 255, 255], 13);
+            }
+            if let Some((index, stage)) = brain.curriculum_stage() {
+                g.draw_text(&format!("课程阶段:{} {}", index + 1, stage.name), 5.0, 39.0, &[255, 255, 255, 255], 13);
+            }
+            if let Some(size) = brain.novelty_archive_size() {
+                g.draw_text(&format!("新颖性存档:{}", size), 5.0, 56.0, &[255, 255, 255, 255], 13);
+            }
+            if self.simulation.population_mode() {
+                let world = self.simulation.world();
+                g.draw_text(&format!("整个种群同时训练(P键切换) 剩余汽车:{}/{}", world.alive_cars(), world.num_cars()), 5.0, 73.0, &[255, 255, 255, 255], 13);
+            }
+
+            //绘制最好的网络结构
+            if self.show_net {
+                if let Some((generation, net)) = self.best_net.as_ref() {
+                    netdraw::draw(g, net, 445.0, 5.0, 150.0, 110.0);
+                    g.draw_text(&format!("第{}代最好的网络(N键隐藏)", generation), 445.0, 118.0, &[255, 255, 255, 255], 11);
+                }
+            }
         } else {
             //绘制得分
             g.draw_text(
-                &format!("得分：{}", self.score),
+                &format!("得分：{}", self.simulation.world().score()),
                 260.0,
                 90.0,
                 &[255, 255, 255, 255],
@@ -604,7 +711,7 @@ impl State for SpaceOut {
             );
 
             //绘制剩余生命
-            for i in 0..self.num_lives {
+            for i in 0..self.simulation.world().num_lives() {
                 g.draw_image(
                     None,
                     &stage.img_sm_car,
@@ -617,7 +724,7 @@ impl State for SpaceOut {
                     ]),
                 );
             }
-            if self.game_over {
+            if self.simulation.world().game_over() {
                 g.draw_image(
                     None,
                     &stage.img_game_over,
@@ -637,121 +744,47 @@ impl State for SpaceOut {
         if self.stage.is_none() {
             return;
         }
-        if !self.game_over {
-            if !self.demo {
-                // 随机添加外星人
-                if rand_int(0, *self.difficulty.borrow()) == 0 {
-                    self.add_alien();
-                }
-            }
+        //进入新的一代或加载了网络后更新网络结构图
+        let brain = self.simulation.brain();
+        let generation = brain.current_generation();
+        if self.best_net.as_ref().map(|(g, _)| *g) != Some(generation) {
+            self.best_net = brain.best_brain().map(|net| (generation, SavedNet::from_net(net, brain.sensors())));
+        }
+        if self.fast_mode {
+            //每一帧在每个CPU核心上评估一个网络，整个种群评估完后进入下一代
+            self.simulation.brain_mut().train_slice(rayon::current_num_threads());
+            return;
+        }
+        if !self.simulation.world().game_over() {
             //更新背景图
             self.background.update();
 
-            //更新精灵
+            //更新游戏模拟
+            let result = self.simulation.step();
+
+            //更新爆炸精灵
             self.update_sprites();
-            
-            //更新AI
-
-            //计算距离最近的外星人子弹
-            let mut closest_alien_so_far = 99999.0;
-            let mut closest_missile_so_far = 99999.0;
-            let mut colosest_alien_pos = (0.0, 0.0);
-            let mut colosest_missile_pos = (0.0, 0.0);
-            let rect = *self.get_sprite(self.car_sprite_id).unwrap().position();
+
             if current_timestamp()>self.next_print_time{
                 self.next_print_time = current_timestamp()+2000.0;
-                println!("汽车位置={:?}", rect.left);
-            }
-            let car_pos = Vector2f::new((rect.left+(rect.right-rect.left)/2.0) as f32, (rect.top+(rect.bottom-rect.top)/2.0) as f32);
-            for sprite in &self.sprites{
-                if sprite.name() == "blobbo"
-                    || sprite.name() == "jelly"
-                    || sprite.name() == "timmy"{
-                    let rect = sprite.position();
-                    let (center_x, center_y) = ((rect.left+(rect.right-rect.left)/2.0) as f32, (rect.top+(rect.bottom-rect.top)/2.0) as f32);
-                    let mpos = Vector2f::new(center_x,  center_y);
-                    let len_to_object = (mpos-car_pos).len();
-                    if len_to_object < closest_alien_so_far {
-                        closest_alien_so_far = len_to_object;
-                        colosest_alien_pos = (mpos.x, mpos.y);
-                    }
-                }
-                if sprite.name() == "amissile"{
-                    let rect = sprite.position();
-                    let (center_x, center_y) = ((rect.left+(rect.right-rect.left)/2.0) as f32, (rect.top+(rect.bottom-rect.top)/2.0) as f32);
-                    let mpos = Vector2f::new(center_x,  center_y);
-                    let len_to_object = (mpos-car_pos).len();
-                    if len_to_object < closest_missile_so_far {
-                        closest_missile_so_far = len_to_object;
-                        colosest_missile_pos = (mpos.x, mpos.y);
-                    }
-                }
-            }
-            let turn = self.brain.update(
-                car_pos.x as f64/CLIENT_WIDTH,
-                colosest_alien_pos.0 as f64/CLIENT_WIDTH, colosest_alien_pos.1 as f64/CLIENT_HEIGHT,
-                colosest_missile_pos.0 as f64/CLIENT_WIDTH, colosest_missile_pos.1 as f64/CLIENT_HEIGHT,
-                );
-            // println!("最近位置{:?} 最近距离:{:?} 汽车位置:{:?} 转向:{:?}", closest_missile_pos, closest_so_far, (car_pos.x, car_pos.y), turn);
-            let (turn, fire) = turn;
-            if fire && self.car_missile_count<10{
-                let car_left_pos = self.get_sprite(self.car_sprite_id).unwrap().position().left;
-                //创建一个新的导弹精灵
-                let mut sprite = Sprite::with_bounds_action(
-                    String::from("missile"),
-                    Resource::Static(self.stage.as_ref().unwrap().img_missile.clone()),
-                    Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
-                    BA_DIE,
-                );
-                sprite.set_position(car_left_pos + 15.0, 400.0);
-                sprite.set_velocity(0.0, -7.0);
-                self.add_sprite(sprite);
-                self.car_missile_count += 1;
-            }
-            let car = self.get_sprite(self.car_sprite_id).unwrap();
-            let (vx, vy) = (car.velocity().x as i32, car.velocity().y);
-            match turn{
-                Turn::Left => {
-                    car.set_velocity(cmp::max(vx-2, -6) as f64, vy);
-                    // self.get_sprite(self.car_sprite_id).unwrap().set_velocity(-6.0, 0.0);
-                },
-                Turn::Right => {
-                    car.set_velocity(cmp::min(vx+2, 6) as f64, vy);
-                    // self.get_sprite(self.car_sprite_id).unwrap().set_velocity(6.0, 0.0);
-                }
+                log!("汽车位置={:?}", self.simulation.world().car().position().left);
             }
 
-            //如果距离小于50，汽车死亡，重新开始demo
-            // if closest_alien_so_far<50.0 || closest_missile_so_far<50.0{
-            //     // println!("汽车死亡！");
-            //     //通知AI汽车死亡
-            //     self.brain.car_dying();
-            //     // for s in &mut self.sprites{
-            //     //      if s.name() != "car"{
-            //     //          s.kill();
-            //     //      }
-            //     // }
-            //     self.sprites.retain(|s|{
-            //         s.name() == "car"
-            //     });
-            //     for _ in 0..6 {
-            //         self.add_alien();
-            //     }
-            // }
-            
+            for event in result.events {
+                self.handle_event(event);
+            }
         } else {
             self.game_over_delay -= 1;
             if self.game_over_delay == 0 {
                 //停止播放背景音乐，转换到演示模式
                 stop_music();
-                self.demo = true;
-                self.new_game();
+                self.new_game(true);
             }
         }
     }
 }
 
-//游戏引擎回调函数
+//游戏引擎回调函数(只管理爆炸效果精灵)
 impl GameEngine for SpaceOut {
     fn sprites_mut(&mut self) -> &mut Vec<Sprite> {
         &mut self.sprites
@@ -760,154 +793,35 @@ impl GameEngine for SpaceOut {
         &self.sprites
     }
     //精灵死亡处理
-    fn sprite_dying(&mut self, sprite_dying_id: usize) {
-        //外星人死亡 添加一个新的外星人
-        
-        if self.sprites[sprite_dying_id].name() == "blobbo"
-                    || self.sprites[sprite_dying_id].name() == "jelly"
-                    || self.sprites[sprite_dying_id].name() == "timmy"{
-            self.add_alien();
-        }
-        let stage = self.stage.as_ref().unwrap();
-
-        //检查是否子弹精灵死亡
-        if self.sprites[sprite_dying_id].name() == "missile"
-            || self.sprites[sprite_dying_id].name() == "amissile"
-        {
-            
-            if self.demo && self.sprites[sprite_dying_id].name() == "missile"{
-                self.car_missile_count -= 1;
-            }
-
-            //播放小的爆炸声音
-            if !self.demo {
-                mengine::play_sound(&stage.sound_sm_explode);
-            }
-            //在子弹位置创建一个小的爆炸精灵
-            let mut frames = vec![];
-            for y in (0..136).step_by(17) {
-                frames.push([0., y as f64, 17., 17.]);
-            }
-            let anim = Animation::active(stage.img_sm_explosion.clone(), frames, 25.0);
-
-            let mut sprite = Sprite::from_bitmap(
-                String::from("sm_explosion"),
-                Resource::Animation(anim),
-                Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
-            );
-            {
-                let dpos = self.sprites[sprite_dying_id].position();
-                sprite.set_position(dpos.left, dpos.top);
-            }
-            self.add_sprite(sprite);
-        }
-    }
+    fn sprite_dying(&mut self, _sprite_dying_id: usize) {}
 
     //碰撞检测
-    fn sprite_collision(&mut self, sprite_hitter_id: usize, sprite_hittee_id: usize) -> bool {
-        //检查是否玩家的子弹和外星人相撞
-        let hitter = self.sprites[sprite_hitter_id].name().to_string();
-        let hittee = self.sprites[sprite_hittee_id].name().to_string();
-        if hitter == "missile" && (hittee == "blobbo" || hittee == "jelly" || hittee == "timmy")
-            || hittee == "missile" && (hitter == "blobbo" || hitter == "jelly" || hitter == "timmy")
-        {
-            //播放小的爆炸声音
-            mengine::play_sound(&self.stage.as_ref().unwrap().sound_sm_explode);
-            //杀死子弹和外星人
-            self.sprites[sprite_hitter_id].kill();
-            self.sprites[sprite_hittee_id].kill();
-
-            //在外星人位置创建一个大的爆炸精灵
-            let pos: &Rect = if hitter == "missile" {
-                self.sprites[sprite_hittee_id].position()
-            } else {
-                self.sprites[sprite_hitter_id].position()
-            };
-
-            let mut frames = vec![];
-            for y in (0..272).step_by(34) {
-                frames.push([0., y as f64, 33., 34.]);
-            }
-            let anim = Animation::active(
-                self.stage.as_ref().unwrap().img_lg_explosion.clone(),
-                frames,
-                25.0,
-            );
-
-            let mut sprite = Sprite::from_bitmap(
-                String::from("lg_explosion"),
-                Resource::Animation(anim),
-                Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
-            );
-            sprite.set_position(pos.left, pos.top);
-            self.add_sprite(sprite);
-
-            //更新得分
-            self.score += 25;
-            // *self.difficulty.borrow_mut() = cmp::max(80 - (self.score / 20), 20);
-        }
-        //检查是否有外星人子弹撞到汽车
-        if hitter == "car" && hittee == "amissile" || hittee == "car" && hitter == "amissile" {
-            //播放大的爆炸声音
-            mengine::play_sound(&self.stage.as_ref().unwrap().sound_lg_explode);
-            //杀死子弹精灵
-            if hitter == "car" {
-                self.sprites[sprite_hittee_id].kill();
-            } else {
-                self.sprites[sprite_hitter_id].kill();
-            }
-
-            //在汽车位置创建一个大的爆炸精灵
-            let pos: &Rect = if hitter == "car" {
-                self.sprites[sprite_hitter_id].position()
-            } else {
-                self.sprites[sprite_hittee_id].position()
-            };
-
-            let mut frames = vec![];
-            for y in (0..272).step_by(34) {
-                frames.push([0., y as f64, 33., 34.]);
-            }
-            let anim = Animation::active(
-                self.stage.as_ref().unwrap().img_lg_explosion.clone(),
-                frames,
-                25.0,
-            );
-
-            let mut sprite = Sprite::from_bitmap(
-                String::from("lg_explosion"),
-                Resource::Animation(anim),
-                Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
-            );
-            sprite.set_position(pos.left, pos.top);
-            self.add_sprite(sprite);
-
-            //移动汽车到起点
-            self.get_sprite(self.car_sprite_id)
-                .unwrap()
-                .set_position(30.0, 405.0);
-
-            if self.demo{
-                //通知AI汽车死亡
-                self.brain.car_dying(self.score);
-                return false;
-            }else{
-                self.num_lives -= 1;
-
-                //检查游戏是否结束
-                if self.num_lives == 0 {
-                    //播放游戏结束声音
-                    mengine::play_sound(&self.stage.as_ref().unwrap().sound_gameover);
-                    self.game_over = true;
-                    self.game_over_delay = 150;
-                }
-            }
-        }
+    fn sprite_collision(&mut self, _sprite_hitter_id: usize, _sprite_hittee_id: usize) -> bool {
         false
     }
 }
 
+//不打开窗口，比较手写规则和加载的网络在多个种子上的表现
+fn run_benchmark(options: &Options, episodes: usize) {
+    let config = config::load_or_exit(options);
+    let seed = config.seed.unwrap_or_else(training::default_seed);
+    log!("随机数种子:{}", seed);
+    let comparison = config.comparison(episodes, seed);
+    comparison.run("scripted", || Box::new(ScriptedController));
+    comparison.run_network(options.load_brain.as_ref(), &config.sensors);
+}
+
 fn main() {
+    //外部驾驶者使用标准输入输出时，解析参数时的错误信息也不能输出到标准输出
+    let args: Vec<String> = std::env::args().collect();
+    if args.windows(2).any(|pair| pair[0] == "--external" && pair[1] == "stdio") {
+        logging::set_to_stderr();
+    }
+    let options = Options::from_args();
+    if let Some(episodes) = options.benchmark {
+        run_benchmark(&options, episodes);
+        return;
+    }
     mengine::run::<SpaceOut>(
         "SpaceOut",
         CLIENT_WIDTH,
@@ -0,0 +1,48 @@
+use super::random::Random;
+use neat::ga::GA;
+use neat::genotype::Genome;
+use serde_derive::{Deserialize, Serialize};
+
+//neat库的变异率(添加神经元、添加连接、扰动权重的概率等)是库中的常量，不能从外部修改
+//这里不改变neat的变异，而是在它之外，每一代繁殖后再按配置扰动一次新一代的连接权重
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeightPerturbation {
+    //每个连接被扰动的概率，0表示只使用neat的变异
+    pub rate: f64,
+    //扰动的最大幅度(-power~power)
+    pub power: f64,
+}
+
+impl Default for WeightPerturbation {
+    fn default() -> WeightPerturbation {
+        WeightPerturbation { rate: 0.0, power: 0.5 }
+    }
+}
+
+//连接和权重完全相同的两个基因组
+fn same_links(a: &Genome, b: &Genome) -> bool {
+    a.links.len() == b.links.len()
+        && a.links
+            .iter()
+            .zip(b.links.iter())
+            .all(|(a, b)| a.innovation_id == b.innovation_id && a.weight == b.weight)
+}
+
+//随机扰动启用的连接权重，然后重新创建网络
+//neat把每个物种最好的基因组原样复制到下一代，这些基因组不扰动，避免丢失最好的网络
+pub fn perturb_weights(ga: &mut GA, rate: f64, power: f64, rng: &mut Random) {
+    let species = &ga.species;
+    for genome in ga.genomes.iter_mut() {
+        if species.iter().any(|species| same_links(&species.leader, genome)) {
+            continue;
+        }
+        for link in genome.links.iter_mut().filter(|link| link.enabled) {
+            if rng.next_f64() < rate {
+                link.weight += (rng.next_f64() * 2.0 - 1.0) * power;
+            }
+        }
+    }
+    ga.create_phenotypes();
+}
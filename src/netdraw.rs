@@ -0,0 +1,143 @@
+use super::persist::{NeuronKind, SavedNet};
+use mengine::Graphics;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//绘制网络结构：游戏中的叠加层，以及每一代保存到磁盘的SVG和Graphviz DOT文件
+//神经元的位置由split_x(横向)和split_y(深度，输入为0，输出为1)决定，输入在下，输出在上
+
+//神经元在区域(x, y, width, height)中的位置
+fn layout(net: &SavedNet, x: f64, y: f64, width: f64, height: f64) -> Vec<(f64, f64)> {
+    net.neurons
+        .iter()
+        .map(|neuron| (x + neuron.split_x * width, y + (1.0 - neuron.split_y) * height))
+        .collect()
+}
+
+fn neuron_color(kind: NeuronKind) -> [u8; 4] {
+    match kind {
+        NeuronKind::Input => [80, 160, 255, 255],
+        NeuronKind::Bias => [160, 160, 160, 255],
+        NeuronKind::Output => [255, 220, 0, 255],
+        _ => [255, 255, 255, 255],
+    }
+}
+
+//正的权重为绿色，负的为红色，绝对值越大越不透明
+fn link_color(weight: f64) -> [u8; 4] {
+    let alpha = 80 + (weight.abs().min(3.0) / 3.0 * 175.0) as u8;
+    if weight >= 0.0 {
+        [0, 200, 0, alpha]
+    } else {
+        [220, 0, 0, alpha]
+    }
+}
+
+fn css_color(color: [u8; 4]) -> String {
+    format!("rgba({},{},{},{:.2})", color[0], color[1], color[2], color[3] as f64 / 255.0)
+}
+
+//用小方块画一条点线
+pub fn dotted_line(g: &mut Graphics, color: &[u8; 4], x1: f64, y1: f64, x2: f64, y2: f64) {
+    let steps = ((x2 - x1).hypot(y2 - y1) / 3.0).max(1.0) as usize;
+    for i in 0..=steps {
+        let t = i as f64 / steps as f64;
+        g.fill_rect(color, x1 + (x2 - x1) * t - 0.5, y1 + (y2 - y1) * t - 0.5, 1.5, 1.5);
+    }
+}
+
+//在游戏画面中绘制网络(连接用点线表示)
+pub fn draw(g: &mut Graphics, net: &SavedNet, x: f64, y: f64, width: f64, height: f64) {
+    //背景
+    g.fill_rect(&[0, 0, 0, 160], x, y, width, height);
+    //留出边距，神经元不画在边框上
+    let margin = 8.0;
+    let positions = layout(net, x + margin, y + margin, width - margin * 2.0, height - margin * 2.0);
+    for link in &net.links {
+        let ((x1, y1), (x2, y2)) = (positions[link.from], positions[link.to]);
+        dotted_line(g, &link_color(link.weight), x1, y1, x2, y2);
+    }
+    for (neuron, &(nx, ny)) in net.neurons.iter().zip(&positions) {
+        g.fill_rect(&neuron_color(neuron.kind), nx - 3.0, ny - 3.0, 6.0, 6.0);
+    }
+}
+
+pub fn to_svg(net: &SavedNet, width: f64, height: f64) -> String {
+    let margin = 12.0;
+    let positions = layout(net, margin, margin, width - margin * 2.0, height - margin * 2.0);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+        width, height
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="black"/>"#);
+    for link in &net.links {
+        let ((x1, y1), (x2, y2)) = (positions[link.from], positions[link.to]);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="1"/>"#,
+            x1,
+            y1,
+            x2,
+            y2,
+            css_color(link_color(link.weight))
+        );
+    }
+    for (neuron, &(x, y)) in net.neurons.iter().zip(&positions) {
+        let _ = writeln!(
+            svg,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="4" fill="{}"><title>{} {:?}</title></circle>"#,
+            x,
+            y,
+            css_color(neuron_color(neuron.kind)),
+            neuron.id,
+            neuron.kind
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn to_dot(net: &SavedNet) -> String {
+    let mut dot = String::from("digraph net {\n    rankdir=BT;\n");
+    for (i, neuron) in net.neurons.iter().enumerate() {
+        let shape = match neuron.kind {
+            NeuronKind::Input | NeuronKind::Bias => "box",
+            NeuronKind::Output => "doublecircle",
+            _ => "circle",
+        };
+        let _ = writeln!(
+            dot,
+            "    n{} [label=\"{} {:?}\" shape={}];",
+            i, neuron.id, neuron.kind, shape
+        );
+    }
+    for link in &net.links {
+        let color = if link.weight >= 0.0 { "darkgreen" } else { "red" };
+        let style = if link.recurrent { " style=dashed" } else { "" };
+        let _ = writeln!(
+            dot,
+            "    n{} -> n{} [label=\"{:.2}\" color={} penwidth={:.1}{}];",
+            link.from,
+            link.to,
+            link.weight,
+            color,
+            0.5 + link.weight.abs().min(3.0),
+            style
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+//把第generation代最好的网络保存为dir/net_00012.svg和dir/net_00012.dot
+pub fn save_generation<P: AsRef<Path>>(dir: P, generation: i32, net: &SavedNet) -> io::Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let name = format!("net_{:05}", generation);
+    fs::write(dir.join(format!("{}.svg", name)), to_svg(net, 240.0, 200.0))?;
+    fs::write(dir.join(format!("{}.dot", name)), to_dot(net))
+}
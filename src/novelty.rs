@@ -0,0 +1,162 @@
+use super::simulation::CLIENT_WIDTH;
+use serde_derive::{Deserialize, Serialize};
+
+//新颖性搜索：比较每个网络的行为和种群、存档中其它行为的差异，差异越大得分越高，
+//避免所有网络都收敛到停在一个位置不动
+
+//汽车位置直方图的格数
+pub const POSITION_BINS: usize = 8;
+//开火时间直方图的格数，每格的帧数
+pub const FIRE_BINS: usize = 4;
+pub const FIRE_BIN_FRAMES: u32 = 30 * 30;
+
+//计算新颖性时比较的最近邻居数量
+const NEAREST_NEIGHBOURS: usize = 15;
+//每一代加入存档的最新颖的行为数量，以及存档的最大数量
+const ARCHIVE_PER_GENERATION: usize = 2;
+const MAX_ARCHIVE_SIZE: usize = 500;
+
+//一局游戏中的行为：汽车在各个位置停留的帧数和各个时间段的开火次数
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Behaviour {
+    positions: [u32; POSITION_BINS],
+    fires: [u32; FIRE_BINS],
+}
+
+impl Behaviour {
+    pub fn record_position(&mut self, x: f64) {
+        let bin = (x / CLIENT_WIDTH * POSITION_BINS as f64).max(0.0) as usize;
+        self.positions[bin.min(POSITION_BINS - 1)] += 1;
+    }
+
+    pub fn record_fire(&mut self, frame: u32) {
+        let bin = (frame / FIRE_BIN_FRAMES) as usize;
+        self.fires[bin.min(FIRE_BINS - 1)] += 1;
+    }
+
+    //行为描述：位置直方图归一化为比例，开火次数除以每格的帧数
+    pub fn descriptor(&self) -> Vec<f64> {
+        let frames: u32 = self.positions.iter().sum();
+        let mut descriptor = Vec::with_capacity(POSITION_BINS + FIRE_BINS);
+        for &count in &self.positions {
+            descriptor.push(if frames > 0 { count as f64 / frames as f64 } else { 0.0 });
+        }
+        for &count in &self.fires {
+            descriptor.push(count as f64 / FIRE_BIN_FRAMES as f64);
+        }
+        descriptor
+    }
+}
+
+//多局行为描述的平均值
+pub fn mean_descriptor(descriptors: &[Vec<f64>]) -> Vec<f64> {
+    let mut mean = vec![0.0; POSITION_BINS + FIRE_BINS];
+    for descriptor in descriptors {
+        for (m, d) in mean.iter_mut().zip(descriptor) {
+            *m += d / descriptors.len() as f64;
+        }
+    }
+    mean
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NoveltyArchive {
+    archive: Vec<Vec<f64>>,
+}
+
+impl NoveltyArchive {
+    pub fn archive_size(&self) -> usize {
+        self.archive.len()
+    }
+
+    //计算这一代每个行为的新颖性(与种群和存档中最近的K个行为的平均距离)，并把最新颖的行为加入存档
+    pub fn evaluate(&mut self, behaviours: &[Vec<f64>]) -> Vec<f64> {
+        let novelty: Vec<f64> = behaviours
+            .iter()
+            .enumerate()
+            .map(|(i, behaviour)| {
+                let mut distances: Vec<f64> = behaviours
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, other)| other)
+                    .chain(self.archive.iter())
+                    .map(|other| distance(behaviour, other))
+                    .collect();
+                distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let k = distances.len().min(NEAREST_NEIGHBOURS);
+                if k == 0 {
+                    0.0
+                } else {
+                    distances[..k].iter().sum::<f64>() / k as f64
+                }
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..novelty.len()).collect();
+        order.sort_by(|&a, &b| novelty[b].partial_cmp(&novelty[a]).unwrap());
+        for &i in order.iter().take(ARCHIVE_PER_GENERATION) {
+            self.archive.push(behaviours[i].clone());
+        }
+        //存档太大时丢弃最早的行为
+        if self.archive.len() > MAX_ARCHIVE_SIZE {
+            let excess = self.archive.len() - MAX_ARCHIVE_SIZE;
+            self.archive.drain(..excess);
+        }
+        novelty
+    }
+}
+
+//把新颖性和得分按最大值归一化后混合，fitness_weight为0时只看新颖性
+pub fn blend(novelty: &[f64], fitness: &[f64], fitness_weight: f64) -> Vec<f64> {
+    let max = |values: &[f64]| values.iter().cloned().fold(0.0, f64::max);
+    let (max_novelty, max_fitness) = (max(novelty), max(fitness));
+    novelty
+        .iter()
+        .zip(fitness)
+        .map(|(n, f)| {
+            let n = if max_novelty > 0.0 { n / max_novelty } else { 0.0 };
+            let f = if max_fitness > 0.0 { f / max_fitness } else { 0.0 };
+            //NEAT的得分需要为正数
+            ((1.0 - fitness_weight) * n + fitness_weight * f) * 1000.0 + 1.0
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn novelty_is_the_mean_distance_to_the_nearest_behaviours() {
+        let mut archive = NoveltyArchive::default();
+        let novelty = archive.evaluate(&[vec![0.0], vec![1.0], vec![3.0]]);
+        assert_eq!(novelty, vec![2.0, 1.5, 2.5]);
+        //最新颖的两个行为进入存档，之后也参与比较
+        assert_eq!(archive.archive_size(), 2);
+        assert_eq!(archive.evaluate(&[vec![0.0]]), vec![1.5]);
+    }
+
+    #[test]
+    fn archive_drops_the_oldest_behaviours() {
+        let mut archive = NoveltyArchive::default();
+        for i in 0..MAX_ARCHIVE_SIZE {
+            archive.evaluate(&[vec![i as f64], vec![-(i as f64)]]);
+        }
+        assert_eq!(archive.archive_size(), MAX_ARCHIVE_SIZE);
+        assert_eq!(archive.archive[0], vec![(MAX_ARCHIVE_SIZE / 2) as f64]);
+    }
+
+    #[test]
+    fn blend_mixes_normalised_novelty_and_fitness() {
+        let (novelty, fitness) = ([1.0, 2.0], [10.0, 0.0]);
+        assert_eq!(blend(&novelty, &fitness, 0.5), vec![751.0, 501.0]);
+        assert_eq!(blend(&novelty, &fitness, 0.0), vec![501.0, 1001.0]);
+        assert_eq!(blend(&novelty, &fitness, 1.0), vec![1001.0, 1.0]);
+        assert_eq!(blend(&[0.0, 0.0], &[0.0, 0.0], 0.5), vec![1.0, 1.0]);
+    }
+}
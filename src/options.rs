@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+//启动参数
+// --load-brain <文件>  启动时加载之前训练好的网络，演示模式直接使用它控制汽车
+// --save-brain <文件>  每一代结束后把最好的网络保存到文件
+// --checkpoint <文件>  定期保存整个种群的检查点
+// --checkpoint-every <代数>  每隔多少代保存一次检查点(默认10)
+// --resume <文件>  从检查点恢复训练
+// --fitness <名字>  网络得分的计算方式：survival、kills、blend(默认)、accuracy、dodge
+// --episodes <局数>  每个网络在不同种子的新游戏世界中玩的局数(默认1)
+// --aggregate <名字>  多局得分的合并方式：mean(默认)、min、trimmed
+// --curriculum  课程训练：从少量慢速的外星人开始，平均得分达到门槛后增加难度
+// --novelty <得分权重>  新颖性搜索：按行为(汽车位置和开火时间)的新颖性选择网络，权重0~1为得分所占的比例
+// --actions <名字>  网络输出的含义：binary(左/右，默认)、idle(左/右/不动)、analog(目标速度)
+// --sense-cooldown  网络输入增加开火冷却
+// --net-dir <目录>  每一代结束后把最好的网络结构图保存为SVG和Graphviz DOT文件
+// --stats <文件>  把每一代的得分统计、物种数量和基因组复杂度写入文件(.csv为CSV，其它为JSON lines)
+// --seed <整数>  随机数种子，相同的种子和相同的输入总是得到相同的游戏
+// --benchmark <局数>  不打开窗口，在多个种子上测试手写规则(和--load-brain加载的网络)的平均表现
+// --driver <名字>  驾驶汽车的控制器：brain(默认)、human、network(需要--load-brain)、scripted、external(需要--external)
+// --external <stdio|tcp:端口>  由外部程序通过标准输入输出或本机TCP端口驾驶汽车(每一帧一行JSON)，没有指定--driver时使用external
+// --config <文件>  从配置文件(.toml或JSON)读取训练参数，命令行参数优先
+// --generations <代数>  spaceout-train训练的代数(默认100，指定--target-fitness时不限)
+// --target-fitness <得分>  spaceout-train在一代的最高得分达到它时停止
+// --learner <名字>  spaceout-rl使用的学习器：qlearning(默认)、dqn
+// --train-episodes <局数>  spaceout-rl训练的局数(默认2000)
+// --record <文件>  人类驾驶时把每一帧的网络输入和动作追加到示范数据文件
+// --dataset <文件>  spaceout-imitate读取的示范数据文件
+// --imitate <文件>  spaceout-imitate保存拟合的网络；训练时用它初始化种群
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub load_brain: Option<PathBuf>,
+    pub save_brain: Option<PathBuf>,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_every: Option<i32>,
+    pub resume: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub fitness: Option<String>,
+    pub stats: Option<PathBuf>,
+    pub episodes: Option<usize>,
+    pub aggregate: Option<String>,
+    pub curriculum: bool,
+    pub novelty: Option<f64>,
+    pub actions: Option<String>,
+    pub sense_cooldown: bool,
+    pub net_dir: Option<PathBuf>,
+    pub driver: Option<String>,
+    pub external: Option<String>,
+    pub benchmark: Option<usize>,
+    pub config: Option<PathBuf>,
+    pub generations: Option<i32>,
+    pub target_fitness: Option<f64>,
+    pub learner: Option<String>,
+    pub train_episodes: Option<usize>,
+    pub record: Option<PathBuf>,
+    pub dataset: Option<PathBuf>,
+    pub imitate: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn from_args() -> Options {
+        Options::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--load-brain" => options.load_brain = args.next().map(PathBuf::from),
+                "--save-brain" => options.save_brain = args.next().map(PathBuf::from),
+                "--checkpoint" => options.checkpoint = args.next().map(PathBuf::from),
+                "--checkpoint-every" => {
+                    match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) => options.checkpoint_every = Some(n),
+                        None => log!("--checkpoint-every 需要一个整数"),
+                    }
+                }
+                "--resume" => options.resume = args.next().map(PathBuf::from),
+                "--stats" => options.stats = args.next().map(PathBuf::from),
+                "--fitness" => options.fitness = args.next(),
+                "--episodes" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.episodes = Some(n),
+                    None => log!("--episodes 需要一个整数"),
+                },
+                "--aggregate" => options.aggregate = args.next(),
+                "--curriculum" => options.curriculum = true,
+                "--actions" => options.actions = args.next(),
+                "--sense-cooldown" => options.sense_cooldown = true,
+                "--net-dir" => options.net_dir = args.next().map(PathBuf::from),
+                "--novelty" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(weight) => options.novelty = Some(weight),
+                    None => log!("--novelty 需要一个0~1之间的数"),
+                },
+                "--driver" => options.driver = args.next(),
+                "--external" => options.external = args.next(),
+                "--config" => options.config = args.next().map(PathBuf::from),
+                "--generations" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.generations = Some(n),
+                    None => log!("--generations 需要一个整数"),
+                },
+                "--target-fitness" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(fitness) => options.target_fitness = Some(fitness),
+                    None => log!("--target-fitness 需要一个数"),
+                },
+                "--learner" => options.learner = args.next(),
+                "--train-episodes" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.train_episodes = Some(n),
+                    None => log!("--train-episodes 需要一个整数"),
+                },
+                "--record" => options.record = args.next().map(PathBuf::from),
+                "--dataset" => options.dataset = args.next().map(PathBuf::from),
+                "--imitate" => options.imitate = args.next().map(PathBuf::from),
+                "--benchmark" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => options.benchmark = Some(n),
+                    None => log!("--benchmark 需要一个整数"),
+                },
+                "--seed" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(seed) => options.seed = Some(seed),
+                    None => log!("--seed 需要一个整数"),
+                },
+                _ => log!("未知参数:{}", arg),
+            }
+        }
+        options
+    }
+}
@@ -0,0 +1,673 @@
+use neat::ga::GA;
+use neat::genes::{LinkGene, NeuronGene};
+use neat::genotype::Genome;
+use neat::innovation::{Innovation, InnovationRecord, InnovationType};
+use neat::phenotype::{Link, NeuralNet, Neuron, NeuronType};
+use neat::species::Species;
+use super::fitness::EpisodeEnd;
+use super::novelty::NoveltyArchive;
+use super::sensors::SensorConfig;
+use super::stagnation::StagnationDetector;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+//网络文件格式(JSON)：
+// {
+//   "version": 1,
+//   "depth": 3,                         //网络深度
+//   "sensors": { "car_x": true, "aliens": 1, ... },   //训练时使用的传感器配置，旧的文件没有这个字段
+//   "neurons": [                        //按网络中的顺序排列的神经元
+//     { "id": 0, "kind": "Input", "activation_response": 4.9, "split_x": 0.0, "split_y": 0.0 }, ...
+//   ],
+//   "links": [                          //连接，from/to 为 neurons 数组的下标
+//     { "from": 0, "to": 4, "weight": -0.35, "recurrent": false }, ...
+//   ]
+// }
+
+pub const NET_FILE_VERSION: u32 = 1;
+pub const CHECKPOINT_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum NeuronKind {
+    Input,
+    Hidden,
+    Output,
+    Bias,
+    None,
+}
+
+impl From<NeuronType> for NeuronKind {
+    fn from(neuron_type: NeuronType) -> NeuronKind {
+        match neuron_type {
+            NeuronType::Input => NeuronKind::Input,
+            NeuronType::Hidden => NeuronKind::Hidden,
+            NeuronType::Output => NeuronKind::Output,
+            NeuronType::Bias => NeuronKind::Bias,
+            NeuronType::None => NeuronKind::None,
+        }
+    }
+}
+
+impl From<NeuronKind> for NeuronType {
+    fn from(kind: NeuronKind) -> NeuronType {
+        match kind {
+            NeuronKind::Input => NeuronType::Input,
+            NeuronKind::Hidden => NeuronType::Hidden,
+            NeuronKind::Output => NeuronType::Output,
+            NeuronKind::Bias => NeuronType::Bias,
+            NeuronKind::None => NeuronType::None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedNeuron {
+    pub id: i32,
+    pub kind: NeuronKind,
+    pub activation_response: f64,
+    pub split_x: f64,
+    pub split_y: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedLink {
+    pub from: usize,
+    pub to: usize,
+    pub weight: f64,
+    pub recurrent: bool,
+}
+
+//可以保存到文件的网络
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedNet {
+    pub version: u32,
+    pub depth: i32,
+    #[serde(default)]
+    pub sensors: Option<SensorConfig>,
+    pub neurons: Vec<SavedNeuron>,
+    pub links: Vec<SavedLink>,
+}
+
+impl SavedNet {
+    pub fn from_net(net: &NeuralNet, sensors: &SensorConfig) -> SavedNet {
+        let neurons = net
+            .neurons
+            .iter()
+            .map(|neuron| SavedNeuron {
+                id: neuron.id,
+                kind: neuron.neuron_type.into(),
+                activation_response: neuron.activation_response,
+                split_x: neuron.split_x,
+                split_y: neuron.split_y,
+            })
+            .collect();
+        //每条连接同时出现在两端神经元中，只保存输入端的一份
+        let links = net
+            .neurons
+            .iter()
+            .flat_map(|neuron| neuron.links_in.iter())
+            .map(|link| SavedLink {
+                from: link.from,
+                to: link.to,
+                weight: link.weight,
+                recurrent: link.recurrent,
+            })
+            .collect();
+        SavedNet {
+            version: NET_FILE_VERSION,
+            depth: net.depth,
+            sensors: Some(sensors.clone()),
+            neurons,
+            links,
+        }
+    }
+
+    pub fn to_net(&self) -> io::Result<NeuralNet> {
+        let mut neurons: Vec<Neuron> = self
+            .neurons
+            .iter()
+            .map(|neuron| {
+                Neuron::new(
+                    neuron.kind.into(),
+                    neuron.id,
+                    neuron.split_y,
+                    neuron.split_x,
+                    neuron.activation_response,
+                )
+            })
+            .collect();
+        for link in &self.links {
+            if link.from >= neurons.len() || link.to >= neurons.len() {
+                return Err(invalid_data("连接指向不存在的神经元"));
+            }
+            let link = Link {
+                from: link.from,
+                to: link.to,
+                weight: link.weight,
+                recurrent: link.recurrent,
+            };
+            neurons[link.from].links_out.push(link.clone());
+            neurons[link.to].links_in.push(link);
+        }
+        Ok(NeuralNet::new(neurons, self.depth))
+    }
+
+    //输入神经元数量
+    pub fn num_inputs(&self) -> usize {
+        self.neurons
+            .iter()
+            .filter(|neuron| neuron.kind == NeuronKind::Input)
+            .count()
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.neurons
+            .iter()
+            .filter(|neuron| neuron.kind == NeuronKind::Output)
+            .count()
+    }
+
+    //检查网络保存时的传感器配置是否和当前配置相同，旧的文件只能检查输入输出数量
+    pub fn check_sensors(&self, sensors: &SensorConfig) -> io::Result<()> {
+        if let Some(saved) = self.sensors.as_ref() {
+            if saved != sensors {
+                return Err(invalid_data(&format!(
+                    "网络使用的传感器配置和当前配置不同，网络:{:?} 当前:{:?}",
+                    saved, sensors
+                )));
+            }
+        }
+        if self.num_inputs() != sensors.num_inputs() || self.num_outputs() != sensors.num_outputs() {
+            return Err(invalid_data(&format!(
+                "网络有{}个输入{}个输出，当前传感器配置需要{}个输入{}个输出",
+                self.num_inputs(),
+                self.num_outputs(),
+                sensors.num_inputs(),
+                sensors.num_outputs()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        //BufWriter被丢弃时会忽略写入错误，需要手动写入并同步到磁盘
+        writer.into_inner()?.sync_all()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<SavedNet> {
+        let reader = BufReader::new(File::open(path)?);
+        let net: SavedNet = serde_json::from_reader(reader)?;
+        if net.version != NET_FILE_VERSION {
+            return Err(invalid_data(&format!("不支持的网络文件版本:{}", net.version)));
+        }
+        Ok(net)
+    }
+}
+
+//训练检查点文件格式(JSON)：保存整个种群，用于恢复长时间的训练
+// {
+//   "version": 1,
+//   "sensors": { "car_x": true, "aliens": 1, ... },   //训练时使用的传感器配置
+//   "generation": 12, "current_brain": 0, "max_score": 830.0,
+//   "fitness_scores": [...],            //当前一代每个网络的得分
+//   "best_brain": { ...网络文件格式... },
+//   "ga": {
+//     "genomes": [{ "genome_id", "neurons": [基因], "links": [基因], "fitness", "species", ... }],
+//     "best_genomes": [...], "species": [...], "innovation": { "innovations": [...], ... },
+//     "generation", "next_genome_id", "next_species_id", "pop_size", "best_ever_fitness", ...
+//   },
+//   "curriculum_stage": 0,
+//   "episode_scores": [...], "episode_behaviours": [...],   //当前网络已经玩完的各局
+//   "behaviours": [...], "episode_ends": [...],             //这一代已经评估的网络的行为和各局的结束原因
+//   "novelty": { "archive": [...] },    //新颖性搜索的存档，不使用时为null
+//   "stagnation": { "best": 830.0, "generations": 3 }      //种群停滞检测的计数
+// }
+//当前网络正在玩的这一局不保存，恢复后从这一局的开头重新开始(使用相同的种子)
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedNeuronGene {
+    pub id: i32,
+    pub kind: NeuronKind,
+    pub recurrent: bool,
+    pub activation_response: f64,
+    pub split_y: f64,
+    pub split_x: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedLinkGene {
+    pub from_neuron: i32,
+    pub to_neuron: i32,
+    pub weight: f64,
+    pub enabled: bool,
+    pub recurrent: bool,
+    pub innovation_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedGenome {
+    pub genome_id: i32,
+    pub neurons: Vec<SavedNeuronGene>,
+    pub links: Vec<SavedLinkGene>,
+    pub fitness: f64,
+    pub adjusted_fitness: f64,
+    pub amount_to_spawn: f64,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+    pub species: i32,
+}
+
+impl SavedGenome {
+    pub fn from_genome(genome: &Genome) -> SavedGenome {
+        SavedGenome {
+            genome_id: genome.genome_id,
+            neurons: genome
+                .neurons
+                .iter()
+                .map(|gene| SavedNeuronGene {
+                    id: gene.id,
+                    kind: gene.neuron_type.into(),
+                    recurrent: gene.recurrent,
+                    activation_response: gene.activation_response,
+                    split_y: gene.split_y,
+                    split_x: gene.split_x,
+                })
+                .collect(),
+            links: genome
+                .links
+                .iter()
+                .map(|gene| SavedLinkGene {
+                    from_neuron: gene.from_neuron,
+                    to_neuron: gene.to_neuron,
+                    weight: gene.weight,
+                    enabled: gene.enabled,
+                    recurrent: gene.recurrent,
+                    innovation_id: gene.innovation_id,
+                })
+                .collect(),
+            fitness: genome.fitness,
+            adjusted_fitness: genome.adjusted_fitness,
+            amount_to_spawn: genome.amount_to_spawn,
+            num_inputs: genome.num_inputs,
+            num_outputs: genome.num_outputs,
+            species: genome.species,
+        }
+    }
+
+    pub fn to_genome(&self) -> Genome {
+        Genome {
+            genome_id: self.genome_id,
+            neurons: self
+                .neurons
+                .iter()
+                .map(|gene| NeuronGene {
+                    id: gene.id,
+                    neuron_type: gene.kind.into(),
+                    recurrent: gene.recurrent,
+                    activation_response: gene.activation_response,
+                    split_y: gene.split_y,
+                    split_x: gene.split_x,
+                })
+                .collect(),
+            links: self
+                .links
+                .iter()
+                .map(|gene| LinkGene {
+                    from_neuron: gene.from_neuron,
+                    to_neuron: gene.to_neuron,
+                    weight: gene.weight,
+                    enabled: gene.enabled,
+                    recurrent: gene.recurrent,
+                    innovation_id: gene.innovation_id,
+                })
+                .collect(),
+            fitness: self.fitness,
+            adjusted_fitness: self.adjusted_fitness,
+            amount_to_spawn: self.amount_to_spawn,
+            num_inputs: self.num_inputs,
+            num_outputs: self.num_outputs,
+            species: self.species,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedSpecies {
+    pub leader: SavedGenome,
+    pub members: Vec<usize>,
+    pub species_id: i32,
+    pub best_fitness: f64,
+    pub average_fitness: f64,
+    pub gens_no_improvement: i32,
+    pub age: i32,
+    pub spawns_rqd: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SavedInnovationType {
+    NewNeuron,
+    NewLink,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedInnovationRecord {
+    pub innovation_type: SavedInnovationType,
+    pub innovation_id: i32,
+    pub neuron_in: i32,
+    pub neuron_out: i32,
+    pub neuron_id: i32,
+    pub neuron_kind: NeuronKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedInnovation {
+    pub innovations: Vec<SavedInnovationRecord>,
+    pub next_neuron_id: i32,
+    pub next_innovation_num: i32,
+}
+
+//遗传算法的全部状态
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedGA {
+    pub genomes: Vec<SavedGenome>,
+    pub best_genomes: Vec<SavedGenome>,
+    pub species: Vec<SavedSpecies>,
+    pub innovation: SavedInnovation,
+    pub generation: i32,
+    pub next_genome_id: i32,
+    pub next_species_id: i32,
+    pub pop_size: i32,
+    pub best_ever_fitness: f64,
+    pub total_fit_adj: f64,
+    pub av_fit_adj: f64,
+    pub fittest_genome: usize,
+}
+
+impl SavedGA {
+    pub fn from_ga(ga: &GA) -> SavedGA {
+        SavedGA {
+            genomes: ga.genomes.iter().map(SavedGenome::from_genome).collect(),
+            best_genomes: ga.best_genomes.iter().map(SavedGenome::from_genome).collect(),
+            species: ga
+                .species
+                .iter()
+                .map(|species| SavedSpecies {
+                    leader: SavedGenome::from_genome(&species.leader),
+                    members: species.members.clone(),
+                    species_id: species.species_id,
+                    best_fitness: species.best_fitness,
+                    average_fitness: species.average_fitness,
+                    gens_no_improvement: species.gens_no_improvement,
+                    age: species.age,
+                    spawns_rqd: species.spawns_rqd,
+                })
+                .collect(),
+            innovation: SavedInnovation {
+                innovations: ga
+                    .innovation
+                    .innovations
+                    .iter()
+                    .map(|record| SavedInnovationRecord {
+                        innovation_type: match record.innovation_type {
+                            InnovationType::NewNeuron => SavedInnovationType::NewNeuron,
+                            InnovationType::NewLink => SavedInnovationType::NewLink,
+                        },
+                        innovation_id: record.innovation_id,
+                        neuron_in: record.neuron_in,
+                        neuron_out: record.neuron_out,
+                        neuron_id: record.neuron_id,
+                        neuron_kind: record.neuron_type.into(),
+                    })
+                    .collect(),
+                next_neuron_id: ga.innovation.next_neuron_id,
+                next_innovation_num: ga.innovation.next_innovation_num,
+            },
+            generation: ga.generation,
+            next_genome_id: ga.next_genome_id,
+            next_species_id: ga.next_species_id,
+            pop_size: ga.pop_size,
+            best_ever_fitness: ga.best_ever_fitness,
+            total_fit_adj: ga.total_fit_adj,
+            av_fit_adj: ga.av_fit_adj,
+            fittest_genome: ga.fittest_genome,
+        }
+    }
+
+    //恢复遗传算法，并重新创建所有网络
+    pub fn to_ga(&self, num_inputs: i32, num_outputs: i32) -> io::Result<GA> {
+        if self.genomes.len() != self.pop_size as usize {
+            return Err(invalid_data("检查点中的种群数量不正确"));
+        }
+        let mut ga = GA::new(self.pop_size, num_inputs, num_outputs);
+        ga.genomes = self.genomes.iter().map(SavedGenome::to_genome).collect();
+        ga.best_genomes = self.best_genomes.iter().map(SavedGenome::to_genome).collect();
+        ga.species = self
+            .species
+            .iter()
+            .map(|species| Species {
+                leader: species.leader.to_genome(),
+                members: species.members.clone(),
+                species_id: species.species_id,
+                best_fitness: species.best_fitness,
+                average_fitness: species.average_fitness,
+                gens_no_improvement: species.gens_no_improvement,
+                age: species.age,
+                spawns_rqd: species.spawns_rqd,
+            })
+            .collect();
+        ga.innovation = Innovation {
+            innovations: self
+                .innovation
+                .innovations
+                .iter()
+                .map(|record| InnovationRecord {
+                    innovation_type: match record.innovation_type {
+                        SavedInnovationType::NewNeuron => InnovationType::NewNeuron,
+                        SavedInnovationType::NewLink => InnovationType::NewLink,
+                    },
+                    innovation_id: record.innovation_id,
+                    neuron_in: record.neuron_in,
+                    neuron_out: record.neuron_out,
+                    neuron_id: record.neuron_id,
+                    neuron_type: record.neuron_kind.into(),
+                })
+                .collect(),
+            next_neuron_id: self.innovation.next_neuron_id,
+            next_innovation_num: self.innovation.next_innovation_num,
+        };
+        ga.generation = self.generation;
+        ga.next_genome_id = self.next_genome_id;
+        ga.next_species_id = self.next_species_id;
+        ga.best_ever_fitness = self.best_ever_fitness;
+        ga.total_fit_adj = self.total_fit_adj;
+        ga.av_fit_adj = self.av_fit_adj;
+        ga.fittest_genome = self.fittest_genome;
+        ga.create_phenotypes();
+        Ok(ga)
+    }
+}
+
+//训练检查点
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Checkpoint {
+    pub version: u32,
+    //旧的检查点没有这个字段，使用添加传感器配置之前的输入
+    #[serde(default = "SensorConfig::legacy")]
+    pub sensors: SensorConfig,
+    pub generation: i32,
+    pub current_brain: usize,
+    pub max_score: f64,
+    pub fitness_scores: Vec<f64>,
+    pub best_brain: Option<SavedNet>,
+    pub ga: SavedGA,
+    //课程训练的阶段，旧的检查点没有这个字段
+    #[serde(default)]
+    pub curriculum_stage: usize,
+    //以下字段让恢复的训练和没有中断的训练相同，旧的检查点没有这些字段
+    #[serde(default)]
+    pub episode_scores: Vec<f64>,
+    #[serde(default)]
+    pub episode_behaviours: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub behaviours: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub episode_ends: Vec<EpisodeEnd>,
+    #[serde(default)]
+    pub novelty: Option<NoveltyArchive>,
+    #[serde(default)]
+    pub stagnation: StagnationDetector,
+}
+
+impl Checkpoint {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        //先写入临时文件再改名，避免写到一半时程序退出损坏旧的检查点
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        //临时文件完整写入磁盘后才能替换旧的检查点
+        writer.into_inner()?.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+        let reader = BufReader::new(File::open(path)?);
+        let checkpoint: Checkpoint = serde_json::from_reader(reader)?;
+        if checkpoint.version != CHECKPOINT_FILE_VERSION {
+            return Err(invalid_data(&format!(
+                "不支持的检查点文件版本:{}",
+                checkpoint.version
+            )));
+        }
+        Ok(checkpoint)
+    }
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("spaceout-{}-{}.json", name, std::process::id()))
+    }
+
+    fn saved_net() -> SavedNet {
+        let neuron = |id, kind| SavedNeuron {
+            id,
+            kind,
+            activation_response: 4.9,
+            split_x: 0.5,
+            split_y: id as f64,
+        };
+        let link = |from, weight| SavedLink {
+            from,
+            to: 2,
+            weight,
+            recurrent: false,
+        };
+        SavedNet {
+            version: NET_FILE_VERSION,
+            depth: 2,
+            sensors: None,
+            neurons: vec![neuron(0, NeuronKind::Input), neuron(1, NeuronKind::Bias), neuron(2, NeuronKind::Output)],
+            links: vec![link(0, -0.35), link(1, 0.125)],
+        }
+    }
+
+    #[test]
+    fn net_survives_save_and_load() {
+        let path = temp_path("net");
+        let net = saved_net();
+        net.save(&path).unwrap();
+        let loaded = SavedNet::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&net).unwrap());
+        assert_eq!((loaded.num_inputs(), loaded.num_outputs()), (1, 1));
+    }
+
+    #[test]
+    fn checkpoint_survives_save_and_load() {
+        let path = temp_path("checkpoint");
+        let genome = SavedGenome {
+            genome_id: 7,
+            neurons: vec![SavedNeuronGene {
+                id: 0,
+                kind: NeuronKind::Input,
+                recurrent: false,
+                activation_response: 4.9,
+                split_y: 0.0,
+                split_x: 0.5,
+            }],
+            links: vec![],
+            fitness: 12.5,
+            adjusted_fitness: 6.25,
+            amount_to_spawn: 1.0,
+            num_inputs: 1,
+            num_outputs: 0,
+            species: 1,
+        };
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_FILE_VERSION,
+            sensors: SensorConfig::default(),
+            generation: 12,
+            current_brain: 0,
+            max_score: 830.0,
+            fitness_scores: vec![12.5],
+            best_brain: Some(saved_net()),
+            ga: SavedGA {
+                genomes: vec![genome.clone()],
+                best_genomes: vec![genome],
+                species: vec![],
+                innovation: SavedInnovation {
+                    innovations: vec![],
+                    next_neuron_id: 3,
+                    next_innovation_num: 2,
+                },
+                generation: 12,
+                next_genome_id: 8,
+                next_species_id: 2,
+                pop_size: 1,
+                best_ever_fitness: 12.5,
+                total_fit_adj: 6.25,
+                av_fit_adj: 6.25,
+                fittest_genome: 0,
+            },
+            curriculum_stage: 2,
+            episode_scores: vec![400.0],
+            episode_behaviours: vec![vec![0.5; 12]],
+            behaviours: vec![],
+            episode_ends: vec![EpisodeEnd::Timeout],
+            novelty: None,
+            stagnation: StagnationDetector::default(),
+        };
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&checkpoint).unwrap());
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn ga_survives_save_and_restore() {
+        let ga = GA::new(10, 3, 2);
+        let saved = SavedGA::from_ga(&ga);
+        let restored = saved.to_ga(3, 2).unwrap();
+        assert_eq!(restored.genomes.len(), 10);
+        assert_eq!(
+            serde_json::to_value(SavedGA::from_ga(&restored)).unwrap(),
+            serde_json::to_value(&saved).unwrap()
+        );
+        //种群数量和基因组数量不一致的检查点无法恢复
+        let mut broken = saved;
+        broken.genomes.pop();
+        assert!(broken.to_ga(3, 2).is_err());
+    }
+}
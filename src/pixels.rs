@@ -0,0 +1,125 @@
+use super::simulation::{Body, SpriteKind, World, CLIENT_HEIGHT, CLIENT_WIDTH};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+//像素观测：把游戏画面缩小为灰度图，外星人、导弹和汽车按位置画成实心方块(不需要GPU和图片资源)
+//用于比较卷积网络等基于图像的智能体和CarBrain
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PixelConfig {
+    //缩小后的分辨率
+    pub width: usize,
+    pub height: usize,
+    //观测中包含最近几帧画面(从旧到新)，用于判断物体的运动方向
+    pub stack: usize,
+}
+
+impl Default for PixelConfig {
+    fn default() -> PixelConfig {
+        PixelConfig {
+            width: 80,
+            height: 60,
+            stack: 4,
+        }
+    }
+}
+
+//各种物体的灰度，背景为0
+fn brightness(kind: SpriteKind) -> f64 {
+    match kind {
+        SpriteKind::Car => 1.0,
+        SpriteKind::Missile => 0.8,
+        SpriteKind::Alien(_) => 0.6,
+        SpriteKind::AlienMissile(_) => 0.4,
+    }
+}
+
+impl PixelConfig {
+    //一帧画面的像素数量
+    pub fn frame_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    //观测的长度(所有叠加的画面)
+    pub fn num_inputs(&self) -> usize {
+        self.frame_size() * self.stack.max(1)
+    }
+
+    //按行存储的灰度画面(0~1)，物体重叠时取较亮的
+    pub fn render(&self, world: &World) -> Vec<f64> {
+        let mut frame = vec![0.0; self.frame_size()];
+        //整个种群同时训练时只画出当前选中的汽车
+        let car = world.car();
+        for body in world.bodies() {
+            if body.kind() == SpriteKind::Car && !std::ptr::eq(body, car) {
+                continue;
+            }
+            self.fill(&mut frame, body);
+        }
+        frame
+    }
+
+    //把物体覆盖的格子填上它的灰度，再小的物体也至少占一个格子
+    fn fill(&self, frame: &mut [f64], body: &Body) {
+        let (sx, sy) = (self.width as f64 / CLIENT_WIDTH, self.height as f64 / CLIENT_HEIGHT);
+        let pos = body.position();
+        let cell = |value: f64, size: usize| (value.max(0.0) as usize).min(size);
+        let (left, top) = (cell(pos.left * sx, self.width), cell(pos.top * sy, self.height));
+        let right = cell(((pos.left + body.width()) * sx).ceil(), self.width).max(left + 1).min(self.width);
+        let bottom = cell(((pos.top + body.height()) * sy).ceil(), self.height).max(top + 1).min(self.height);
+        let value = brightness(body.kind());
+        for y in top..bottom {
+            for x in left..right {
+                let pixel = &mut frame[y * self.width + x];
+                *pixel = pixel.max(value);
+            }
+        }
+    }
+}
+
+//最近几帧画面
+#[derive(Clone, Debug)]
+pub struct FrameStack {
+    config: PixelConfig,
+    frames: VecDeque<Vec<f64>>,
+}
+
+impl FrameStack {
+    pub fn new(config: PixelConfig) -> FrameStack {
+        FrameStack {
+            config,
+            frames: VecDeque::new(),
+        }
+    }
+
+    //新的一局开始时用第一帧填满
+    pub fn reset(&mut self, world: &World) {
+        let frame = self.config.render(world);
+        self.frames.clear();
+        for _ in 0..self.config.stack.max(1) {
+            self.frames.push_back(frame.clone());
+        }
+    }
+
+    pub fn push(&mut self, world: &World) {
+        if self.frames.len() >= self.config.stack.max(1) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(self.config.render(world));
+    }
+
+    //所有画面首尾相接(从旧到新)
+    pub fn observation(&self) -> Vec<f64> {
+        self.frames.iter().flat_map(|frame| frame.iter().cloned()).collect()
+    }
+
+    //最新的一帧画面
+    pub fn latest(&self) -> Option<&Vec<f64>> {
+        self.frames.back()
+    }
+
+    pub fn config(&self) -> &PixelConfig {
+        &self.config
+    }
+}
@@ -0,0 +1,226 @@
+use super::ai::{decide, Action, Turn};
+use super::controller::CarController;
+use super::env::{Observation, RewardConfig};
+use super::logging;
+use super::sensors::SensorConfig;
+use super::simulation::{GameEvent, World};
+use serde_derive::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+//外部驾驶者：由其它语言编写的程序通过标准输入输出或本机TCP端口驾驶汽车
+//每一帧发送一行JSON(观测、奖励、是否结束)，然后等待一行JSON格式的动作
+//  发送: {"frame":0,"observation":[...],"reward":0.01,"done":false,"game_over":false,"score":0,"can_fire":true}
+//  接收: {"turn":"left","fire":true}  turn可以是left、right、idle
+//        {"speed":-3.5,"fire":false}  以目标速度(像素/帧)行驶，优先于turn
+//        {"output":[0.8,0.2]}  和网络输出相同，按--actions的动作模式转换为动作
+//观测和CarBrain的网络输入相同(见sensors.rs)，奖励的计算方式和强化学习环境相同(见env.rs)
+//done表示汽车被击中：汽车回到起点继续行驶(非演示模式少一条命)，不需要客户端重新开始
+//一局在游戏结束时结束：最后发送一帧done和game_over都为true的消息，不等待回复
+//使用标准输入输出时，游戏的日志输出到标准错误，标准输出只有每一帧的JSON
+
+//连接方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endpoint {
+    //标准输入输出，游戏的日志改为输出到标准错误
+    Stdio,
+    //监听127.0.0.1上的端口，等待一个客户端连接
+    Tcp(u16),
+}
+
+impl Endpoint {
+    //stdio或tcp:端口
+    pub fn parse(name: &str) -> Option<Endpoint> {
+        if name == "stdio" {
+            return Some(Endpoint::Stdio);
+        }
+        name.strip_prefix("tcp:")
+            .and_then(|port| port.parse().ok())
+            .map(Endpoint::Tcp)
+    }
+}
+
+//每一帧发送给客户端的消息
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub frame: u32,
+    pub observation: Observation,
+    //上一个动作之后发生的事件得到的奖励
+    pub reward: f64,
+    //汽车被击中(之后汽车回到起点)
+    pub done: bool,
+    //游戏结束，这是最后一帧，不需要回复
+    pub game_over: bool,
+    pub score: i32,
+    pub can_fire: bool,
+}
+
+//客户端发送的动作
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Command {
+    pub turn: Option<String>,
+    pub speed: Option<f64>,
+    pub fire: bool,
+    pub output: Option<Vec<f64>>,
+}
+
+impl Command {
+    pub fn to_action(&self, sensors: &SensorConfig) -> Option<Action> {
+        if let Some(output) = self.output.as_ref() {
+            if output.len() < sensors.actions.num_outputs() {
+                return None;
+            }
+            return Some(decide(output, sensors.actions));
+        }
+        let turn = match (self.speed, self.turn.as_deref()) {
+            (Some(speed), _) => Turn::Target(speed),
+            (None, Some("left")) => Turn::Left,
+            (None, Some("right")) => Turn::Right,
+            (None, Some("idle")) | (None, None) => Turn::Idle,
+            _ => return None,
+        };
+        Some(Action::new(turn, self.fire))
+    }
+}
+
+pub struct RemoteController {
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+    sensors: SensorConfig,
+    reward: RewardConfig,
+    //上一个动作之后发生的事件
+    events: Vec<GameEvent>,
+    frame: u32,
+    //客户端断开后汽车停在原地
+    connected: bool,
+}
+
+impl RemoteController {
+    pub fn new(
+        reader: Box<dyn BufRead>,
+        writer: Box<dyn Write>,
+        sensors: SensorConfig,
+        reward: RewardConfig,
+    ) -> RemoteController {
+        RemoteController {
+            reader,
+            writer,
+            sensors,
+            reward,
+            events: vec![],
+            frame: 0,
+            connected: true,
+        }
+    }
+
+    //TCP端口会阻塞直到客户端连接
+    pub fn connect(endpoint: Endpoint, sensors: SensorConfig, reward: RewardConfig) -> io::Result<RemoteController> {
+        match endpoint {
+            Endpoint::Stdio => {
+                logging::set_to_stderr();
+                Ok(RemoteController::new(
+                    Box::new(BufReader::new(io::stdin())),
+                    Box::new(io::stdout()),
+                    sensors,
+                    reward,
+                ))
+            }
+            Endpoint::Tcp(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", port))?;
+                eprintln!("等待外部驾驶者连接 127.0.0.1:{}", port);
+                let (stream, addr) = listener.accept()?;
+                eprintln!("外部驾驶者已连接:{}", addr);
+                RemoteController::from_stream(stream, sensors, reward)
+            }
+        }
+    }
+
+    pub fn from_stream(stream: TcpStream, sensors: SensorConfig, reward: RewardConfig) -> io::Result<RemoteController> {
+        //每一帧都要等待回复，关闭Nagle算法减少延迟
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(RemoteController::new(
+            Box::new(reader),
+            Box::new(stream),
+            sensors,
+            reward,
+        ))
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    //发送这一帧的观测、奖励和上一个动作之后的事件
+    fn send_frame(&mut self, world: &World, game_over: bool) -> io::Result<()> {
+        let frame = Frame {
+            frame: self.frame,
+            observation: self.sensors.observe(world),
+            reward: self.reward.reward(&self.events),
+            done: game_over
+                || self
+                    .events
+                    .iter()
+                    .any(|event| matches!(event, GameEvent::CarHit { .. })),
+            game_over,
+            score: world.score(),
+            can_fire: world.can_fire(),
+        };
+        self.events.clear();
+        self.frame += 1;
+        serde_json::to_writer(&mut self.writer, &frame)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    //发送这一帧的观测，读取客户端的动作(无法识别时为None)
+    fn exchange(&mut self, world: &World) -> io::Result<Option<Action>> {
+        self.send_frame(world, false)?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "连接已关闭"));
+        }
+        let action = serde_json::from_str::<Command>(&line)
+            .ok()
+            .and_then(|command| command.to_action(&self.sensors));
+        if action.is_none() {
+            eprintln!("无效的动作:{}", line.trim());
+        }
+        Ok(action)
+    }
+}
+
+impl CarController for RemoteController {
+    fn name(&self) -> &'static str {
+        "external"
+    }
+    fn act(&mut self, world: &World) -> Action {
+        if !self.connected {
+            return Action::new(Turn::Idle, false);
+        }
+        match self.exchange(world) {
+            Ok(Some(action)) => action,
+            //无效的动作不开火也不移动
+            Ok(None) => Action::new(Turn::Idle, false),
+            Err(err) => {
+                //标准输出可能是通信通道，错误信息输出到标准错误
+                eprintln!("外部驾驶者断开:{:?}", err);
+                self.connected = false;
+                Action::new(Turn::Idle, false)
+            }
+        }
+    }
+    fn record(&mut self, events: &[GameEvent]) {
+        self.events.extend_from_slice(events);
+    }
+    fn game_over(&mut self, world: &World) {
+        if !self.connected {
+            return;
+        }
+        if let Err(err) = self.send_frame(world, true) {
+            eprintln!("外部驾驶者断开:{:?}", err);
+            self.connected = false;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use super::ai::ActionMode;
+use super::simulation::{SpriteKind, World, CLIENT_HEIGHT, CLIENT_WIDTH, MAX_CAR_SPEED};
+use serde_derive::{Deserialize, Serialize};
+
+//汽车能"看到"的内容和网络输出的含义，网络的输入输出数量由它决定
+
+//一种目标(外星人或外星人子弹)的中心位置，由近到远
+pub type Targets = Vec<(f64, f64)>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorConfig {
+    //汽车位置(x/width)
+    pub car_x: bool,
+    //汽车速度(vx/最大速度)
+    pub car_velocity: bool,
+    //最近的K个外星人位置
+    pub aliens: usize,
+    //最近的K个外星人子弹位置
+    pub alien_missiles: usize,
+    //已发射的导弹数量(count/最大数量)
+    pub missile_count: bool,
+    //外星人和子弹使用相对于汽车的位置(dx/width, dy/height)，否则使用绝对位置
+    pub relative: bool,
+    //距离下一次可以开火的帧数(frames/冷却帧数)
+    pub fire_cooldown: bool,
+    //网络输出的含义
+    pub actions: ActionMode,
+}
+
+impl Default for SensorConfig {
+    fn default() -> SensorConfig {
+        SensorConfig {
+            car_x: true,
+            car_velocity: false,
+            aliens: 1,
+            alien_missiles: 1,
+            missile_count: false,
+            relative: false,
+            fire_cooldown: false,
+            actions: ActionMode::Binary,
+        }
+    }
+}
+
+impl SensorConfig {
+    //添加传感器配置之前网络使用的输入：汽车位置和最近的外星人子弹位置
+    pub fn legacy() -> SensorConfig {
+        SensorConfig {
+            aliens: 0,
+            ..SensorConfig::default()
+        }
+    }
+
+    //网络输入数量
+    pub fn num_inputs(&self) -> usize {
+        let mut count = (self.aliens + self.alien_missiles) * 2;
+        if self.car_x {
+            count += 1;
+        }
+        if self.car_velocity {
+            count += 1;
+        }
+        if self.missile_count {
+            count += 1;
+        }
+        if self.fire_cooldown {
+            count += 1;
+        }
+        count
+    }
+
+    //网络输出数量
+    pub fn num_outputs(&self) -> usize {
+        self.actions.num_outputs()
+    }
+
+    //汽车"看到"的外星人和外星人子弹的中心位置(由近到远，最多K个)
+    pub fn targets(&self, world: &World) -> (Targets, Targets) {
+        let car_pos = world.car().center();
+        let mut aliens = vec![];
+        let mut alien_missiles = vec![];
+        for body in world.bodies() {
+            let list = match body.kind() {
+                SpriteKind::Alien(_) => &mut aliens,
+                SpriteKind::AlienMissile(_) => &mut alien_missiles,
+                _ => continue,
+            };
+            let pos = body.center();
+            list.push(((pos - car_pos).len(), pos.x as f64, pos.y as f64));
+        }
+        let nearest = |mut list: Vec<(f32, f64, f64)>, count: usize| -> Vec<(f64, f64)> {
+            list.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            list.into_iter().take(count).map(|(_, x, y)| (x, y)).collect()
+        };
+        (nearest(aliens, self.aliens), nearest(alien_missiles, self.alien_missiles))
+    }
+
+    //每个输入的名字，用于显示
+    pub fn input_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        if self.car_x {
+            names.push("汽车x".to_string());
+        }
+        if self.car_velocity {
+            names.push("汽车速度".to_string());
+        }
+        for i in 1..=self.aliens {
+            names.push(format!("外星人{}x", i));
+            names.push(format!("外星人{}y", i));
+        }
+        for i in 1..=self.alien_missiles {
+            names.push(format!("子弹{}x", i));
+            names.push(format!("子弹{}y", i));
+        }
+        if self.missile_count {
+            names.push("导弹数量".to_string());
+        }
+        if self.fire_cooldown {
+            names.push("开火冷却".to_string());
+        }
+        names
+    }
+
+    //从模拟中读取网络输入
+    //顺序：汽车位置、汽车速度、外星人(由近到远)、外星人子弹(由近到远)、导弹数量、开火冷却
+    pub fn observe(&self, world: &World) -> Vec<f64> {
+        let mut inputs = Vec::with_capacity(self.num_inputs());
+        let car = world.car();
+        let car_pos = car.center();
+        if self.car_x {
+            inputs.push(car_pos.x as f64 / CLIENT_WIDTH);
+        }
+        if self.car_velocity {
+            inputs.push(car.velocity().x / MAX_CAR_SPEED);
+        }
+
+        let car_pos = (car_pos.x as f64, car_pos.y as f64);
+        let (aliens, alien_missiles) = self.targets(world);
+        for (list, count) in &[(aliens, self.aliens), (alien_missiles, self.alien_missiles)] {
+            for i in 0..*count {
+                match list.get(i) {
+                    Some(&(x, y)) => {
+                        if self.relative {
+                            inputs.push((x - car_pos.0) / CLIENT_WIDTH);
+                            inputs.push((y - car_pos.1) / CLIENT_HEIGHT);
+                        } else {
+                            inputs.push(x / CLIENT_WIDTH);
+                            inputs.push(y / CLIENT_HEIGHT);
+                        }
+                    }
+                    //没有这么多目标时，绝对位置填0，相对位置填一个很远的位置
+                    None => {
+                        let missing = if self.relative { 1.0 } else { 0.0 };
+                        inputs.push(missing);
+                        inputs.push(missing);
+                    }
+                }
+            }
+        }
+
+        if self.missile_count {
+            inputs.push(world.car_missile_count() as f64 / world.config().max_car_missiles.max(1) as f64);
+        }
+        if self.fire_cooldown {
+            inputs.push(world.fire_cooldown() as f64 / world.config().fire_cooldown.max(1) as f64);
+        }
+        inputs
+    }
+}
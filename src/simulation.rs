@@ -0,0 +1,927 @@
+use super::ai::{Action, CarBrain, Turn};
+use super::alien_sprite;
+use super::arena::Arena;
+use super::controller::CarController;
+use math2d::Vector2f;
+use super::random::Random;
+use mengine::{Point, Rect};
+use serde_derive::{Deserialize, Serialize};
+
+//无窗口的游戏模拟核心：不依赖Window、Graphics、图片和声音，可以在没有显示器的机器上运行
+
+pub const CLIENT_WIDTH: f64 = 600.0;
+pub const CLIENT_HEIGHT: f64 = 450.0;
+
+//外星人子弹落地时与汽车的水平距离小于它算作擦肩而过
+pub const NEAR_MISS_DISTANCE: f64 = 30.0;
+
+//击落一个外星人的得分
+pub const ALIEN_SCORE: i32 = 25;
+
+//汽车最多同时存在的导弹数量(默认值)
+pub const MAX_CAR_MISSILES: i32 = 10;
+
+//汽车两次开火之间至少间隔的帧数(默认值)
+pub const FIRE_COOLDOWN: i32 = 5;
+
+//汽车的最大速度和每帧的加速度(像素/帧)
+pub const MAX_CAR_SPEED: f64 = 6.0;
+pub const CAR_ACCELERATION: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Alien {
+    Blobbo,
+    Jelly,
+    Timmy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpriteKind {
+    Car,
+    Missile,
+    Alien(Alien),
+    //外星人子弹，记录发射它的外星人种类
+    AlienMissile(Alien),
+}
+
+impl SpriteKind {
+    //精灵尺寸(与图片素材的单帧尺寸一致)
+    pub fn size(&self) -> (f64, f64) {
+        match self {
+            SpriteKind::Car => (37.0, 18.0),
+            SpriteKind::Missile => (5.0, 18.0),
+            SpriteKind::Alien(Alien::Blobbo) => (32.0, 34.0),
+            SpriteKind::Alien(Alien::Jelly) => (33.0, 33.0),
+            SpriteKind::Alien(Alien::Timmy) => (33.0, 17.0),
+            SpriteKind::AlienMissile(Alien::Blobbo) => (5.0, 16.0),
+            SpriteKind::AlienMissile(Alien::Jelly) => (9.0, 16.0),
+            SpriteKind::AlienMissile(Alien::Timmy) => (16.0, 14.0),
+        }
+    }
+
+    pub fn is_alien(&self) -> bool {
+        matches!(self, SpriteKind::Alien(_))
+    }
+}
+
+//精灵到达边界时的动作
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundsAction {
+    Wrap,
+    Bounce,
+    Die,
+}
+
+//模拟中的精灵
+#[derive(Clone, Debug)]
+pub struct Body {
+    id: usize,
+    kind: SpriteKind,
+    position: Rect,
+    velocity: Point,
+    bounds: Rect,
+    bounds_action: BoundsAction,
+    age: u32,
+    dying: bool,
+    //汽车导弹属于哪一辆汽车
+    owner: usize,
+    //外星人子弹因为击中汽车而消失(不算擦过)
+    hit_car: bool,
+}
+
+impl Body {
+    pub fn new(id: usize, kind: SpriteKind, bounds: Rect, bounds_action: BoundsAction) -> Body {
+        let (width, height) = kind.size();
+        Body {
+            id,
+            kind,
+            position: Rect::new(0.0, 0.0, width, height),
+            velocity: Point { x: 0.0, y: 0.0 },
+            bounds,
+            bounds_action,
+            age: 0,
+            dying: false,
+            owner: 0,
+            hit_car: false,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn kind(&self) -> SpriteKind {
+        self.kind
+    }
+
+    pub fn position(&self) -> &Rect {
+        &self.position
+    }
+
+    pub fn velocity(&self) -> &Point {
+        &self.velocity
+    }
+
+    pub fn width(&self) -> f64 {
+        self.position.right - self.position.left
+    }
+
+    pub fn height(&self) -> f64 {
+        self.position.bottom - self.position.top
+    }
+
+    //存活的帧数，用于选择动画帧
+    pub fn age(&self) -> u32 {
+        self.age
+    }
+
+    pub fn center(&self) -> Vector2f {
+        Vector2f::new(
+            (self.position.left + self.width() / 2.0) as f32,
+            (self.position.top + self.height() / 2.0) as f32,
+        )
+    }
+
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        let (width, height) = (self.width(), self.height());
+        self.position = Rect::new(x, y, x + width, y + height);
+    }
+
+    pub fn set_velocity(&mut self, x: f64, y: f64) {
+        self.velocity = Point { x, y };
+    }
+
+    pub fn kill(&mut self) {
+        self.dying = true;
+    }
+
+    //移动精灵并处理边界动作
+    fn update(&mut self) {
+        self.age += 1;
+        let (width, height) = (self.width(), self.height());
+        let mut x = self.position.left + self.velocity.x;
+        let mut y = self.position.top + self.velocity.y;
+        let bounds = self.bounds;
+        match self.bounds_action {
+            BoundsAction::Wrap => {
+                if x + width < bounds.left {
+                    x = bounds.right;
+                } else if x > bounds.right {
+                    x = bounds.left - width;
+                }
+                if y + height < bounds.top {
+                    y = bounds.bottom;
+                } else if y > bounds.bottom {
+                    y = bounds.top - height;
+                }
+            }
+            BoundsAction::Bounce => {
+                if x < bounds.left {
+                    x = bounds.left;
+                    self.velocity.x = -self.velocity.x;
+                } else if x + width > bounds.right {
+                    x = bounds.right - width;
+                    self.velocity.x = -self.velocity.x;
+                }
+                if y < bounds.top {
+                    y = bounds.top;
+                    self.velocity.y = -self.velocity.y;
+                } else if y + height > bounds.bottom {
+                    y = bounds.bottom - height;
+                    self.velocity.y = -self.velocity.y;
+                }
+            }
+            BoundsAction::Die => {
+                if x + width < bounds.left
+                    || x > bounds.right
+                    || y + height < bounds.top
+                    || y > bounds.bottom
+                {
+                    self.dying = true;
+                }
+            }
+        }
+        self.set_position(x, y);
+    }
+
+    //碰撞矩形比精灵略小
+    fn collision_rect(&self) -> Rect {
+        let (shrink_x, shrink_y) = (self.width() / 12.0, self.height() / 12.0);
+        Rect::new(
+            self.position.left + shrink_x,
+            self.position.top + shrink_y,
+            self.position.right - shrink_x,
+            self.position.bottom - shrink_y,
+        )
+    }
+
+    fn test_collision(&self, other: &Body) -> bool {
+        let (a, b) = (self.collision_rect(), other.collision_rect());
+        a.left <= b.right && b.left <= a.right && a.top <= b.bottom && b.top <= a.bottom
+    }
+}
+
+//一帧中发生的事件，窗口程序根据事件播放声音和爆炸动画
+//car为事件所属汽车的序号(只有一辆汽车时为0)
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    //汽车发射了导弹(pos为导弹位置)
+    MissileFired { x: f64, y: f64, car: usize },
+    //导弹或外星人子弹消失，产生小爆炸
+    MissileExploded { x: f64, y: f64 },
+    //外星人被car发射的导弹击中，产生大爆炸
+    AlienKilled { x: f64, y: f64, car: usize },
+    //汽车被击中，产生大爆炸
+    CarHit { x: f64, y: f64, car: usize },
+    //外星人子弹从汽车旁边擦过
+    NearMiss { car: usize },
+    GameOver,
+}
+
+impl GameEvent {
+    //事件所属的汽车
+    pub fn car(&self) -> Option<usize> {
+        match *self {
+            GameEvent::MissileFired { car, .. }
+            | GameEvent::AlienKilled { car, .. }
+            | GameEvent::CarHit { car, .. }
+            | GameEvent::NearMiss { car } => Some(car),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StepResult {
+    pub events: Vec<GameEvent>,
+}
+
+impl StepResult {
+    //这一帧汽车是否被击中
+    pub fn car_hit(&self) -> bool {
+        self.events.iter().any(|event| matches!(event, GameEvent::CarHit { .. }))
+    }
+}
+
+//游戏世界的难度
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    //演示模式开始时的外星人数量
+    pub aliens: usize,
+    //会出现的外星人种类
+    pub alien_types: Vec<Alien>,
+    //外星人开火和出现的频率，越小越频繁
+    pub difficulty: i32,
+    //汽车最多同时存在的导弹数量
+    pub max_car_missiles: i32,
+    //汽车两次开火之间至少间隔的帧数
+    pub fire_cooldown: i32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> WorldConfig {
+        WorldConfig {
+            aliens: 6,
+            alien_types: vec![Alien::Jelly],
+            difficulty: 80,
+            max_car_missiles: MAX_CAR_MISSILES,
+            fire_cooldown: FIRE_COOLDOWN,
+        }
+    }
+}
+
+//游戏世界：所有精灵、得分和游戏规则，汽车由外部的动作控制
+//一辆汽车的状态
+#[derive(Clone, Debug)]
+struct CarState {
+    id: usize,
+    missile_count: i32, //最多10发导弹
+    //距离下一次可以开火的帧数
+    fire_cooldown: i32,
+    alive: bool,
+}
+
+impl CarState {
+    fn new(id: usize) -> CarState {
+        CarState {
+            id,
+            missile_count: 0,
+            fire_cooldown: 0,
+            alive: true,
+        }
+    }
+}
+
+pub struct World {
+    bodies: Vec<Body>,
+    next_id: usize,
+    //所有汽车，car()、drive()等方法作用于选择的那一辆
+    cars: Vec<CarState>,
+    selected_car: usize,
+    num_cars: usize,
+    //汽车被击中后退出(整个种群同时训练)，否则回到起点
+    drop_out: bool,
+    num_lives: i32,
+    score: i32,
+    demo: bool,
+    config: WorldConfig,
+    game_over: bool,
+    rng: Random,
+}
+
+impl World {
+    //相同的种子和相同的输入总是得到相同的游戏
+    pub fn new(demo: bool, seed: u64) -> World {
+        World::with_config(demo, seed, WorldConfig::default())
+    }
+
+    //使用指定的难度创建游戏世界
+    pub fn with_config(demo: bool, seed: u64, config: WorldConfig) -> World {
+        World::create(demo, seed, config, 1, false)
+    }
+
+    //演示模式下count辆汽车共享同一群外星人，每辆汽车只会被外星人子弹击中，被击中后退出
+    pub fn with_cars(seed: u64, config: WorldConfig, count: usize) -> World {
+        World::create(true, seed, config, count.max(1), true)
+    }
+
+    fn create(demo: bool, seed: u64, config: WorldConfig, num_cars: usize, drop_out: bool) -> World {
+        let mut world = World {
+            bodies: vec![],
+            next_id: 0,
+            cars: vec![],
+            selected_car: 0,
+            num_cars,
+            drop_out,
+            num_lives: 3,
+            score: 0,
+            demo,
+            config,
+            game_over: false,
+            rng: Random::new(seed),
+        };
+        world.new_game(demo);
+        world
+    }
+
+    //新游戏
+    pub fn new_game(&mut self, demo: bool) {
+        //清除所有精灵
+        self.bodies.clear();
+        //初始化游戏变量
+        self.demo = demo;
+        self.score = 0;
+        self.num_lives = 3;
+        self.game_over = false;
+
+        //创建汽车
+        self.cars.clear();
+        self.selected_car = 0;
+        for _ in 0..self.num_cars {
+            let mut car = self.create_body(
+                SpriteKind::Car,
+                Rect::new(40.0, 0.0, CLIENT_WIDTH - 40.0, CLIENT_HEIGHT),
+                BoundsAction::Wrap,
+            );
+            self.cars.push(CarState::new(car.id()));
+            car.set_position(300.0, 405.0);
+            self.bodies.push(car);
+        }
+
+        if self.demo {
+            //添加一些外星人
+            for _ in 0..self.config.aliens {
+                self.add_alien();
+            }
+        }
+    }
+
+    pub fn bodies(&self) -> &Vec<Body> {
+        &self.bodies
+    }
+
+    //选择的汽车(必须还没有退出)
+    pub fn car(&self) -> &Body {
+        let car_id = self.cars[self.selected_car].id;
+        self.bodies.iter().find(|b| b.id == car_id).unwrap()
+    }
+
+    fn car_mut(&mut self) -> &mut Body {
+        let car_id = self.cars[self.selected_car].id;
+        self.bodies.iter_mut().find(|b| b.id == car_id).unwrap()
+    }
+
+    pub fn num_cars(&self) -> usize {
+        self.cars.len()
+    }
+
+    //选择car()、drive()等方法作用的汽车
+    pub fn select_car(&mut self, index: usize) {
+        self.selected_car = index;
+    }
+
+    pub fn car_alive(&self, index: usize) -> bool {
+        self.cars[index].alive
+    }
+
+    pub fn alive_cars(&self) -> usize {
+        self.cars.iter().filter(|car| car.alive).count()
+    }
+
+    //汽车退出(超时或停滞)
+    pub fn remove_car(&mut self, index: usize) {
+        let car_id = self.cars[index].id;
+        self.cars[index].alive = false;
+        self.bodies.retain(|body| body.id != car_id);
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn num_lives(&self) -> i32 {
+        self.num_lives
+    }
+
+    pub fn car_missile_count(&self) -> i32 {
+        self.cars[self.selected_car].missile_count
+    }
+
+    pub fn fire_cooldown(&self) -> i32 {
+        self.cars[self.selected_car].fire_cooldown
+    }
+
+    pub fn config(&self) -> &WorldConfig {
+        &self.config
+    }
+
+    //汽车现在能否开火
+    pub fn can_fire(&self) -> bool {
+        let car = &self.cars[self.selected_car];
+        car.fire_cooldown == 0 && car.missile_count < self.config.max_car_missiles
+    }
+
+    pub fn demo(&self) -> bool {
+        self.demo
+    }
+
+    pub fn game_over(&self) -> bool {
+        self.game_over
+    }
+
+    fn create_body(&mut self, kind: SpriteKind, bounds: Rect, bounds_action: BoundsAction) -> Body {
+        let body = Body::new(self.next_id, kind, bounds, bounds_action);
+        self.next_id += 1;
+        body
+    }
+
+    //添加外星人
+    fn add_alien(&mut self) {
+        //创建一个随机的外星人精灵
+        let bounds = Rect::new(0.0, 0.0, CLIENT_WIDTH, 410.0);
+        //只有一种外星人时不消耗随机数
+        let types = &self.config.alien_types;
+        let kind = match types.len() {
+            0 => Alien::Jelly,
+            1 => types[0],
+            len => types[self.rng.rand_int(0, len as i32) as usize],
+        };
+        let alien = match kind {
+            Alien::Blobbo => {
+                // Blobbo
+                let mut body = self.create_body(
+                    SpriteKind::Alien(Alien::Blobbo),
+                    bounds,
+                    BoundsAction::Bounce,
+                );
+                body.set_position(
+                    match self.rng.rand_int(0, 2) {
+                        0 => 0.0,
+                        _ => 600.0,
+                    },
+                    self.rng.rand_int(0, 370) as f64,
+                );
+                body.set_velocity(self.rng.rand_int(0, 7) as f64 - 2.0, self.rng.rand_int(0, 7) as f64 - 2.0);
+                body
+            }
+            Alien::Jelly => {
+                // Jelly
+                let mut body = self.create_body(
+                    SpriteKind::Alien(Alien::Jelly),
+                    bounds,
+                    BoundsAction::Bounce,
+                );
+                body.set_position(
+                    self.rng.rand_int(0, CLIENT_WIDTH as i32) as f64,
+                    self.rng.rand_int(0, 370) as f64,
+                );
+                body.set_velocity(self.rng.rand_int(0, 5) as f64 - 2., self.rng.rand_int(0, 5) as f64 + 3.);
+                body
+            }
+            Alien::Timmy => {
+                // Timmy
+                let mut body = self.create_body(
+                    SpriteKind::Alien(Alien::Timmy),
+                    bounds,
+                    BoundsAction::Wrap,
+                );
+                body.set_position(
+                    self.rng.rand_int(0, CLIENT_WIDTH as i32) as f64,
+                    self.rng.rand_int(0, 370) as f64,
+                );
+                body.set_velocity(self.rng.rand_int(0, 7) as f64 + 3., 0.);
+                body
+            }
+        };
+        self.bodies.push(alien);
+    }
+
+    //汽车发射导弹
+    fn fire_missile(&mut self, events: &mut Vec<GameEvent>) {
+        if !self.can_fire() {
+            return;
+        }
+        let index = self.selected_car;
+        self.cars[index].fire_cooldown = self.config.fire_cooldown;
+        let car_left_pos = self.car().position().left;
+        //创建一个新的导弹精灵
+        let mut missile = self.create_body(
+            SpriteKind::Missile,
+            Rect::new(0.0, 0.0, CLIENT_WIDTH, CLIENT_HEIGHT),
+            BoundsAction::Die,
+        );
+        missile.set_position(car_left_pos + 15.0, 400.0);
+        missile.set_velocity(0.0, -7.0);
+        missile.owner = index;
+        events.push(GameEvent::MissileFired {
+            x: missile.position().left,
+            y: missile.position().top,
+            car: index,
+        });
+        self.bodies.push(missile);
+        self.cars[index].missile_count += 1;
+    }
+
+    //更新一帧：添加外星人、移动精灵和碰撞，汽车由drive()控制
+    pub fn update(&mut self) -> StepResult {
+        let mut events = vec![];
+        if self.game_over {
+            return StepResult { events };
+        }
+        for car in self.cars.iter_mut() {
+            if car.fire_cooldown > 0 {
+                car.fire_cooldown -= 1;
+            }
+        }
+
+        if !self.demo {
+            // 随机添加外星人
+            if self.rng.rand_int(0, self.config.difficulty) == 0 {
+                self.add_alien();
+            }
+        }
+
+        //更新精灵
+        self.update_bodies(&mut events);
+
+        StepResult { events }
+    }
+
+    //执行汽车的动作(转向和开火)，冷却中或导弹数量已满时不会开火
+    pub fn drive(&mut self, action: Action, events: &mut Vec<GameEvent>) {
+        if self.game_over {
+            return;
+        }
+        if action.fire {
+            self.fire_missile(events);
+        }
+        let car = self.car_mut();
+        let (vx, vy) = (car.velocity().x, car.velocity().y);
+        //每帧速度最多改变CAR_ACCELERATION
+        let target = match action.turn {
+            Turn::Left => -MAX_CAR_SPEED,
+            Turn::Right => MAX_CAR_SPEED,
+            Turn::Idle => 0.0,
+            Turn::Target(speed) => speed.clamp(-MAX_CAR_SPEED, MAX_CAR_SPEED),
+        };
+        let dv = (target - vx).clamp(-CAR_ACCELERATION, CAR_ACCELERATION);
+        car.set_velocity(vx + dv, vy);
+    }
+
+    //移动精灵、发射外星人子弹、检测碰撞并移除死亡的精灵
+    fn update_bodies(&mut self, events: &mut Vec<GameEvent>) {
+        let mut new_bodies = vec![];
+        for i in 0..self.bodies.len() {
+            self.bodies[i].update();
+            //检查外星人是否要发射子弹
+            if let SpriteKind::Alien(alien) = self.bodies[i].kind {
+                if alien_sprite::wants_to_fire(&mut self.rng, self.config.difficulty) {
+                    let missile = alien_sprite::create_missile(self.next_id, alien, &self.bodies[i]);
+                    self.next_id += 1;
+                    new_bodies.push(missile);
+                }
+            }
+        }
+        self.bodies.append(&mut new_bodies);
+
+        //碰撞检测
+        for i in 0..self.bodies.len() {
+            for j in i + 1..self.bodies.len() {
+                if self.bodies[i].dying || self.bodies[j].dying {
+                    continue;
+                }
+                if self.bodies[i].test_collision(&self.bodies[j]) {
+                    self.body_collision(i, j, events);
+                }
+            }
+        }
+
+        //精灵死亡处理
+        let mut i = 0;
+        while i < self.bodies.len() {
+            if self.bodies[i].dying {
+                let body = self.bodies.remove(i);
+                self.body_dying(&body, events);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    fn body_dying(&mut self, body: &Body, events: &mut Vec<GameEvent>) {
+        match body.kind {
+            //外星人死亡 添加一个新的外星人
+            SpriteKind::Alien(_) => self.add_alien(),
+            //子弹精灵死亡，在子弹位置产生一个小的爆炸
+            SpriteKind::Missile | SpriteKind::AlienMissile(_) => {
+                if body.kind == SpriteKind::Missile {
+                    //原来只在演示模式减少，玩家发射10发导弹后就再也不能开火；现在所有模式都减少
+                    self.cars[body.owner].missile_count -= 1;
+                } else if !body.hit_car {
+                    //没有击中汽车但是离汽车很近
+                    for (index, car) in self.cars.iter().enumerate().filter(|(_, car)| car.alive) {
+                        let car = match self.bodies.iter().find(|b| b.id == car.id) {
+                            Some(car) => car,
+                            None => continue,
+                        };
+                        if !body.test_collision(car)
+                            && (body.center().x - car.center().x).abs() as f64 <= NEAR_MISS_DISTANCE
+                        {
+                            events.push(GameEvent::NearMiss { car: index });
+                        }
+                    }
+                }
+                events.push(GameEvent::MissileExploded {
+                    x: body.position.left,
+                    y: body.position.top,
+                });
+            }
+            SpriteKind::Car => (),
+        }
+    }
+
+    fn body_collision(&mut self, hitter: usize, hittee: usize, events: &mut Vec<GameEvent>) {
+        let (hitter_kind, hittee_kind) = (self.bodies[hitter].kind, self.bodies[hittee].kind);
+        //检查是否玩家的子弹和外星人相撞
+        if hitter_kind == SpriteKind::Missile && hittee_kind.is_alien()
+            || hittee_kind == SpriteKind::Missile && hitter_kind.is_alien()
+        {
+            //杀死子弹和外星人
+            self.bodies[hitter].kill();
+            self.bodies[hittee].kill();
+
+            //在外星人位置产生一个大的爆炸
+            let (alien, missile) = if hitter_kind == SpriteKind::Missile {
+                (hittee, hitter)
+            } else {
+                (hitter, hittee)
+            };
+            let pos = self.bodies[alien].position;
+            events.push(GameEvent::AlienKilled {
+                x: pos.left,
+                y: pos.top,
+                car: self.bodies[missile].owner,
+            });
+
+            //更新得分
+            self.score += ALIEN_SCORE;
+        }
+        //检查是否有外星人子弹撞到汽车
+        let is_alien_missile = |kind: SpriteKind| matches!(kind, SpriteKind::AlienMissile(_));
+        if hitter_kind == SpriteKind::Car && is_alien_missile(hittee_kind)
+            || hittee_kind == SpriteKind::Car && is_alien_missile(hitter_kind)
+        {
+            //杀死子弹精灵
+            let (car, missile) = if hitter_kind == SpriteKind::Car {
+                (hitter, hittee)
+            } else {
+                (hittee, hitter)
+            };
+            let car_id = self.bodies[car].id;
+            let index = self.cars.iter().position(|state| state.id == car_id).unwrap();
+
+            //在汽车位置产生一个大的爆炸
+            let pos = self.bodies[car].position;
+            events.push(GameEvent::CarHit {
+                x: pos.left,
+                y: pos.top,
+                car: index,
+            });
+
+            if self.drop_out {
+                //汽车退出，子弹继续下落(每辆汽车都像在自己的游戏世界中一样)
+                self.bodies[car].kill();
+                self.cars[index].alive = false;
+                return;
+            }
+            self.bodies[missile].kill();
+            //汽车会被移动到起点，这颗子弹不再算作从汽车旁边擦过
+            self.bodies[missile].hit_car = true;
+
+            //移动汽车到起点
+            self.bodies[car].set_position(30.0, 405.0);
+
+            if !self.demo {
+                self.num_lives -= 1;
+
+                //检查游戏是否结束
+                if self.num_lives == 0 {
+                    self.game_over = true;
+                    events.push(GameEvent::GameOver);
+                }
+            }
+        }
+    }
+}
+
+//游戏模拟：游戏世界加上控制汽车的大脑
+pub struct Simulation {
+    world: World,
+    brain: CarBrain,
+    //驾驶汽车的控制器，为None时由brain驾驶(演示模式下一边驾驶一边训练)
+    driver: Option<Box<dyn CarController>>,
+    //整个种群同时训练时的场地，使用它自己的游戏世界
+    arena: Option<Arena>,
+}
+
+impl Simulation {
+    //brain需要先设置好种子和训练参数，演示模式的第一局和之后的每一局一样使用它决定的游戏世界
+    pub fn new(brain: CarBrain) -> Simulation {
+        Simulation {
+            world: World::with_config(true, brain.episode_seed(), brain.world_config()),
+            brain,
+            driver: None,
+            arena: None,
+        }
+    }
+
+    pub fn new_game(&mut self, demo: bool) {
+        if !demo {
+            self.arena = None;
+        }
+        if demo && self.driver.is_none() {
+            //训练时每一局使用由代数决定的新游戏世界
+            self.world = World::with_config(true, self.brain.episode_seed(), self.brain.world_config());
+        } else {
+            self.world.new_game(demo);
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        match self.arena.as_ref() {
+            Some(arena) => arena.world(),
+            None => &self.world,
+        }
+    }
+
+    pub fn brain(&self) -> &CarBrain {
+        &self.brain
+    }
+
+    pub fn brain_mut(&mut self) -> &mut CarBrain {
+        &mut self.brain
+    }
+
+    pub fn set_brain(&mut self, brain: CarBrain) {
+        self.brain = brain;
+    }
+
+    //选择驾驶者，None表示由正在训练的brain驾驶
+    pub fn set_driver(&mut self, driver: Option<Box<dyn CarController>>) {
+        if driver.is_some() {
+            self.arena = None;
+        }
+        self.driver = driver;
+    }
+
+    //整个种群同时训练(只在演示模式由brain驾驶时有效)，关闭时回到逐个网络训练
+    pub fn set_population_mode(&mut self, enabled: bool) {
+        if enabled && self.world.demo() && self.driver.is_none() {
+            self.arena = Some(Arena::new(&mut self.brain));
+        } else {
+            self.arena = None;
+        }
+    }
+
+    pub fn population_mode(&self) -> bool {
+        self.arena.is_some()
+    }
+
+    pub fn driver_name(&self) -> &'static str {
+        match self.driver.as_ref() {
+            Some(driver) => driver.name(),
+            None => self.brain.name(),
+        }
+    }
+
+    //模拟一帧，返回这一帧发生的事件
+    //没有单独的输入参数：汽车的动作由驾驶者(set_driver)决定，人类玩家的键盘和鼠标由HumanController读取
+    pub fn step(&mut self) -> StepResult {
+        if let Some(arena) = self.arena.as_mut() {
+            return arena.step(&mut self.brain);
+        }
+        let mut result = self.world.update();
+        if self.world.game_over() {
+            //游戏结束的那一帧通知驾驶者，之后的帧没有事件
+            if let Some(driver) = self.driver.as_mut() {
+                if result.events.iter().any(|event| matches!(event, GameEvent::GameOver)) {
+                    driver.record(&result.events);
+                    driver.game_over(&self.world);
+                }
+            }
+            return result;
+        }
+        //只有brain驾驶演示模式时才训练
+        let training = self.world.demo() && self.driver.is_none();
+        if training {
+            self.brain.record(&result.events);
+            if result.car_hit() || self.brain.episode_end().is_some() {
+                //通知AI汽车死亡(或这一局超时、停滞)，下一局(或下一个网络)在新的游戏世界中开始
+                self.brain.car_dying();
+                self.world = World::with_config(true, self.brain.episode_seed(), self.brain.world_config());
+            }
+        }
+
+        if training {
+            self.brain.record_position(self.world.car().center().x as f64);
+        }
+        if let Some(driver) = self.driver.as_mut() {
+            driver.record(&result.events);
+        }
+
+        //驾驶者控制汽车
+        let action = match self.driver.as_mut() {
+            Some(driver) => driver.act(&self.world),
+            None => self.brain.act(&self.world),
+        };
+        let mut events = vec![];
+        self.world.drive(action, &mut events);
+        if training {
+            self.brain.record(&events);
+        }
+        if let Some(driver) = self.driver.as_mut() {
+            driver.record(&events);
+        }
+        result.events.append(&mut events);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //每一帧所有精灵的位置、得分、生命和事件
+    fn play(seed: u64, demo: bool, frames: usize) -> Vec<String> {
+        let mut world = World::new(demo, seed);
+        let mut log = vec![];
+        for frame in 0..frames {
+            let turn = match frame / 30 % 3 {
+                0 => Turn::Left,
+                1 => Turn::Right,
+                _ => Turn::Idle,
+            };
+            let mut result = world.update();
+            world.drive(Action::new(turn, frame % 7 == 0), &mut result.events);
+            let bodies: Vec<_> = world
+                .bodies()
+                .iter()
+                .map(|body| (body.id(), body.kind(), body.position().left, body.position().top))
+                .collect();
+            log.push(format!("{:?} {} {} {:?}", bodies, world.score(), world.num_lives(), result.events));
+        }
+        log
+    }
+
+    #[test]
+    fn same_seed_and_actions_replay_the_same_game() {
+        for &demo in &[true, false] {
+            let first = play(7, demo, 3000);
+            assert_eq!(first, play(7, demo, 3000));
+            assert_ne!(first, play(8, demo, 3000));
+        }
+    }
+}
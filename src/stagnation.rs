@@ -0,0 +1,154 @@
+use super::fitness::{EpisodeEnd, EpisodeStats};
+use super::mutation;
+use super::random::Random;
+use super::training::EpisodeLimits;
+use neat::ga::GA;
+use serde_derive::{Deserialize, Serialize};
+
+//停滞检测：一局中长时间没有进展时提前结束，种群多代没有进步时重新生成种群或加大变异
+
+//汽车离开上一次有进展的位置超过这个距离才算移动(二值输出的网络会在原地左右抖动)
+const MOVE_DISTANCE: f64 = 40.0;
+
+//判断一局是否应该结束：达到最大帧数，或者stagnation_frames帧内没有击落外星人也没有移动
+#[derive(Clone, Debug, Default)]
+pub struct EpisodeMonitor {
+    anchor_x: Option<f64>,
+    kills: u32,
+    progress_frame: u32,
+}
+
+impl EpisodeMonitor {
+    //每一帧调用一次，返回这一局结束的原因
+    pub fn update(&mut self, limits: &EpisodeLimits, stats: &EpisodeStats, car_x: f64) -> Option<EpisodeEnd> {
+        if stats.frames >= limits.max_frames {
+            return Some(EpisodeEnd::Timeout);
+        }
+        if limits.stagnation_frames == 0 {
+            return None;
+        }
+        let moved = self.anchor_x.is_none_or(|x| (car_x - x).abs() >= MOVE_DISTANCE);
+        if moved || stats.aliens_killed != self.kills {
+            self.anchor_x = Some(car_x);
+            self.kills = stats.aliens_killed;
+            self.progress_frame = stats.frames;
+        }
+        if stats.frames - self.progress_frame >= limits.stagnation_frames {
+            Some(EpisodeEnd::Stagnation)
+        } else {
+            None
+        }
+    }
+}
+
+//种群停滞后的处理方式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StagnationAction {
+    //重新生成整个种群(最好的网络仍然保存在CarBrain中用于演示和保存，最高分不变，但它不会进入新的种群)
+    Reseed,
+    //随机扰动连接权重(每个物种最好的基因组除外)
+    Boost,
+}
+
+//种群停滞的判断条件和处理方式
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PopulationStagnation {
+    //最高得分连续多少代没有提高算作停滞，0表示不检测
+    pub generations: u32,
+    //最高得分至少提高这个比例才算进步
+    pub min_improvement: f64,
+    pub action: StagnationAction,
+    //加大变异时每个连接被扰动的概率和扰动的最大幅度
+    pub boost_rate: f64,
+    pub boost_power: f64,
+}
+
+impl Default for PopulationStagnation {
+    fn default() -> PopulationStagnation {
+        PopulationStagnation {
+            generations: 0,
+            min_improvement: 0.01,
+            action: StagnationAction::Boost,
+            boost_rate: 0.3,
+            boost_power: 1.0,
+        }
+    }
+}
+
+//记录种群的最高得分多少代没有提高(保存在检查点中)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StagnationDetector {
+    best: Option<f64>,
+    generations: u32,
+}
+
+impl StagnationDetector {
+    //每一代调用一次，停滞时返回true并重新计数
+    pub fn update(&mut self, settings: &PopulationStagnation, best: f64) -> bool {
+        if settings.generations == 0 {
+            return false;
+        }
+        let improved = self
+            .best
+            .is_none_or(|previous| best > previous + previous.abs() * settings.min_improvement);
+        if improved {
+            self.best = Some(best);
+            self.generations = 0;
+            return false;
+        }
+        self.generations += 1;
+        if self.generations < settings.generations {
+            return false;
+        }
+        self.generations = 0;
+        true
+    }
+
+    //游戏难度改变后得分不再可比，重新计数
+    pub fn reset(&mut self) {
+        *self = StagnationDetector::default();
+    }
+}
+
+//neat库的变异率是常量，这里直接扰动基因组的连接权重，然后重新创建网络
+pub fn boost_mutation(ga: &mut GA, settings: &PopulationStagnation, rng: &mut Random) {
+    mutation::perturb_weights(ga, settings.boost_rate, settings.boost_power, rng);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detector_triggers_after_flat_generations_and_resets_on_improvement() {
+        let settings = PopulationStagnation {
+            generations: 3,
+            ..PopulationStagnation::default()
+        };
+        let mut detector = StagnationDetector::default();
+        assert!(!detector.update(&settings, 100.0));
+        //提高不到1%不算进步
+        assert!(!detector.update(&settings, 100.5));
+        assert!(!detector.update(&settings, 100.0));
+        //有进步后重新计数
+        assert!(!detector.update(&settings, 200.0));
+        assert!(!detector.update(&settings, 200.0));
+        assert!(!detector.update(&settings, 200.0));
+        assert!(detector.update(&settings, 200.0));
+        //触发后重新计数
+        assert!(!detector.update(&settings, 200.0));
+        assert!(!detector.update(&settings, 200.0));
+        assert!(detector.update(&settings, 200.0));
+    }
+
+    #[test]
+    fn detector_is_off_by_default() {
+        let settings = PopulationStagnation::default();
+        let mut detector = StagnationDetector::default();
+        for _ in 0..100 {
+            assert!(!detector.update(&settings, 0.0));
+        }
+    }
+}
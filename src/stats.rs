@@ -0,0 +1,172 @@
+use super::persist::invalid_data;
+use serde_derive::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+//每一代的训练统计，写入CSV或JSON lines文件用于绘制学习曲线
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct GenerationStats {
+    pub generation: i32,
+    pub best: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub worst: f64,
+    //物种数量
+    pub species: usize,
+    //基因组平均神经元数量和连接数量
+    pub mean_neurons: f64,
+    pub mean_links: f64,
+    //最复杂的基因组的连接数量
+    pub max_links: usize,
+    //因为超时和停滞而结束的局数
+    pub timeouts: usize,
+    pub stagnations: usize,
+}
+
+impl GenerationStats {
+    //scores为这一代所有网络的得分，genome_sizes为每个基因组的(神经元数量, 连接数量)
+    pub fn new(
+        generation: i32,
+        scores: &[f64],
+        species: usize,
+        genome_sizes: &[(usize, usize)],
+    ) -> GenerationStats {
+        let mut stats = GenerationStats {
+            generation,
+            species,
+            ..Default::default()
+        };
+        if !scores.is_empty() {
+            let mut sorted = scores.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let len = sorted.len();
+            stats.worst = sorted[0];
+            stats.best = sorted[len - 1];
+            stats.mean = sorted.iter().sum::<f64>() / len as f64;
+            //长度为奇数时两个下标相同
+            stats.median = (sorted[(len - 1) / 2] + sorted[len / 2]) / 2.0;
+        }
+        if !genome_sizes.is_empty() {
+            let count = genome_sizes.len() as f64;
+            stats.mean_neurons = genome_sizes.iter().map(|s| s.0).sum::<usize>() as f64 / count;
+            stats.mean_links = genome_sizes.iter().map(|s| s.1).sum::<usize>() as f64 / count;
+            stats.max_links = genome_sizes.iter().map(|s| s.1).max().unwrap_or(0);
+        }
+        stats
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsFormat {
+    Csv,
+    JsonLines,
+}
+
+const CSV_HEADER: &str = "generation,best,mean,median,worst,species,mean_neurons,mean_links,max_links,timeouts,stagnations";
+
+//统计文件，扩展名为.csv时写CSV，否则每行写一个JSON对象
+pub struct StatsWriter {
+    writer: BufWriter<File>,
+    format: StatsFormat,
+}
+
+impl StatsWriter {
+    //追加到已有文件，新文件的CSV格式先写表头
+    //已有的CSV文件表头和当前的列不同时(旧版本写的文件)返回错误，避免数据和表头对不上
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<StatsWriter> {
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => StatsFormat::Csv,
+            _ => StatsFormat::JsonLines,
+        };
+        let header = if format == StatsFormat::Csv { first_line(path)? } else { None };
+        if let Some(header) = header.as_ref() {
+            if header != CSV_HEADER {
+                return Err(invalid_data(&format!(
+                    "统计文件的表头和当前的列不同，请使用新的文件:{}",
+                    path.display()
+                )));
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = StatsWriter {
+            writer: BufWriter::new(file),
+            format,
+        };
+        if header.is_none() && format == StatsFormat::Csv {
+            writeln!(writer.writer, "{}", CSV_HEADER)?;
+        }
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        match self.format {
+            StatsFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                stats.generation,
+                stats.best,
+                stats.mean,
+                stats.median,
+                stats.worst,
+                stats.species,
+                stats.mean_neurons,
+                stats.mean_links,
+                stats.max_links,
+                stats.timeouts,
+                stats.stagnations
+            )?,
+            StatsFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, stats)?;
+                writeln!(self.writer)?;
+            }
+        }
+        //每一代都写入磁盘，程序中途退出也不会丢失
+        self.writer.flush()
+    }
+}
+
+//文件的第一行，文件不存在或为空时为None
+fn first_line(path: &Path) -> io::Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    let line = line.trim_end();
+    Ok(if line.is_empty() { None } else { Some(line.to_string()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generation_stats_summarise_scores_and_genomes() {
+        let stats = GenerationStats::new(3, &[5.0, 1.0, 3.0], 2, &[(4, 3), (6, 7)]);
+        assert_eq!((stats.best, stats.mean, stats.median, stats.worst), (5.0, 3.0, 3.0, 1.0));
+        assert_eq!((stats.species, stats.mean_neurons, stats.mean_links, stats.max_links), (2, 5.0, 5.0, 7));
+        //偶数个得分时取中间两个的平均值
+        assert_eq!(GenerationStats::new(3, &[4.0, 1.0, 3.0, 10.0], 1, &[]).median, 3.5);
+        assert_eq!(GenerationStats::new(3, &[], 0, &[]).median, 0.0);
+    }
+
+    #[test]
+    fn csv_appends_only_under_the_current_header() {
+        let path = std::env::temp_dir().join(format!("spaceout-stats-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let stats = GenerationStats::new(1, &[1.0], 1, &[]);
+        StatsWriter::open(&path).unwrap().write(&stats).unwrap();
+        StatsWriter::open(&path).unwrap().write(&stats).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(text.lines().next(), Some(CSV_HEADER));
+
+        //没有timeouts和stagnations两列的旧文件
+        std::fs::write(&path, "generation,best,mean,median,worst,species,mean_neurons,mean_links,max_links\n").unwrap();
+        assert!(StatsWriter::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use super::ai::{decide, Action};
+use super::controller::{CarController, NetController};
+use super::env::{EnvConfig, SpaceOutEnv};
+use super::fitness::{EpisodeEnd, EpisodeStats, FitnessFunction, WeightedBlend};
+use super::random::Random;
+use super::sensors::SensorConfig;
+use super::simulation::{World, WorldConfig, ALIEN_SCORE};
+use neat::phenotype::{NeuralNet, RunType};
+use rayon::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//无窗口训练：每个网络在自己的游戏世界中运行，整个种群在所有CPU核心上并行评估
+
+//一局最多运行的帧数(默认值)，防止一直躲避子弹的网络永远不结束
+pub const MAX_EPISODE_FRAMES: u32 = 30 * 60 * 10;
+//没有击落外星人也没有移动多少帧后结束这一局(默认值)
+pub const STAGNATION_FRAMES: u32 = 30 * 30;
+
+//一局游戏的结束条件(除了汽车被击中)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EpisodeLimits {
+    pub max_frames: u32,
+    //0表示不检测停滞
+    pub stagnation_frames: u32,
+}
+
+impl Default for EpisodeLimits {
+    fn default() -> EpisodeLimits {
+        EpisodeLimits {
+            max_frames: MAX_EPISODE_FRAMES,
+            stagnation_frames: STAGNATION_FRAMES,
+        }
+    }
+}
+
+//在新的游戏世界中玩一局，直到汽车第一次被击中(或超时、停滞)，act根据游戏世界给出动作
+fn play_episode<F: FnMut(&World) -> Action>(
+    config: &WorldConfig,
+    limits: &EpisodeLimits,
+    seed: u64,
+    mut act: F,
+) -> EpisodeStats {
+    let mut env = SpaceOutEnv::new(EnvConfig {
+        world: config.clone(),
+        limits: limits.clone(),
+        ..EnvConfig::default()
+    });
+    env.reset(seed);
+    while !env.done() {
+        let action = act(env.world());
+        env.act(action);
+    }
+    *env.stats()
+}
+
+//用一个网络玩一局
+pub fn run_episode(
+    net: &mut NeuralNet,
+    sensors: &SensorConfig,
+    config: &WorldConfig,
+    limits: &EpisodeLimits,
+    seed: u64,
+) -> EpisodeStats {
+    play_episode(config, limits, seed, |world| {
+        let inputs = sensors.observe(world);
+        decide(&net.update(&inputs, RunType::Active), sensors.actions)
+    })
+}
+
+//用任意驾驶者玩一局
+pub fn run_controller(
+    controller: &mut dyn CarController,
+    config: &WorldConfig,
+    limits: &EpisodeLimits,
+    seed: u64,
+) -> EpisodeStats {
+    play_episode(config, limits, seed, |world| controller.act(world))
+}
+
+//并行评估所有网络，每个网络在每个种子的新游戏世界中各玩一局，结果按网络顺序返回
+pub fn evaluate_population(
+    nets: &mut [NeuralNet],
+    sensors: &SensorConfig,
+    config: &WorldConfig,
+    limits: &EpisodeLimits,
+    seeds: &[u64],
+) -> Vec<Vec<EpisodeStats>> {
+    nets.par_iter_mut()
+        .map(|net| {
+            seeds
+                .iter()
+                .map(|&seed| run_episode(net, sensors, config, limits, seed))
+                .collect()
+        })
+        .collect()
+}
+
+//驾驶者在多个种子上的平均表现
+#[derive(Clone, Debug, Default)]
+pub struct BenchmarkResult {
+    //平均存活帧数
+    pub mean_frames: f64,
+    pub mean_kills: f64,
+    //平均游戏得分(每个外星人25分)
+    pub mean_score: f64,
+    //平均网络得分(使用训练时的得分函数)，可以直接和最高分比较
+    pub mean_fitness: f64,
+    pub worst_fitness: f64,
+    pub best_fitness: f64,
+    //因为超时和停滞而结束的局数
+    pub timeouts: usize,
+    pub stagnations: usize,
+}
+
+impl BenchmarkResult {
+    //打印用的一行摘要
+    pub fn summary(&self) -> String {
+        format!(
+            "平均存活帧数:{:.1} 平均击落:{:.2} 平均游戏得分:{:.1} 网络得分 平均:{:.1} 最低:{:.1} 最高:{:.1} 超时:{} 停滞:{}",
+            self.mean_frames,
+            self.mean_kills,
+            self.mean_score,
+            self.mean_fitness,
+            self.worst_fitness,
+            self.best_fitness,
+            self.timeouts,
+            self.stagnations
+        )
+    }
+
+    pub fn new(results: &[EpisodeStats], fitness: &dyn FitnessFunction) -> BenchmarkResult {
+        if results.is_empty() {
+            return BenchmarkResult::default();
+        }
+        let count = results.len() as f64;
+        let scores: Vec<f64> = results.iter().map(|stats| fitness.fitness(stats)).collect();
+        BenchmarkResult {
+            mean_frames: results.iter().map(|stats| stats.frames as f64).sum::<f64>() / count,
+            mean_kills: results.iter().map(|stats| stats.aliens_killed as f64).sum::<f64>() / count,
+            mean_score: results.iter().map(|stats| (stats.aliens_killed as i32 * ALIEN_SCORE) as f64).sum::<f64>() / count,
+            mean_fitness: scores.iter().sum::<f64>() / count,
+            worst_fitness: scores.iter().cloned().fold(f64::INFINITY, f64::min),
+            best_fitness: scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            timeouts: results.iter().filter(|stats| stats.end == EpisodeEnd::Timeout).count(),
+            stagnations: results.iter().filter(|stats| stats.end == EpisodeEnd::Stagnation).count(),
+        }
+    }
+}
+
+//由seed生成episodes个种子，每个种子用create创建的新驾驶者玩一局，并行运行
+pub fn benchmark<F>(
+    create: F,
+    config: &WorldConfig,
+    limits: &EpisodeLimits,
+    episodes: usize,
+    seed: u64,
+    fitness: &dyn FitnessFunction,
+) -> BenchmarkResult
+where
+    F: Fn() -> Box<dyn CarController> + Sync,
+{
+    let mut rng = Random::new(seed);
+    let seeds: Vec<u64> = (0..episodes).map(|_| rng.next_u64()).collect();
+    let results: Vec<EpisodeStats> = seeds
+        .par_iter()
+        .map(|&seed| run_controller(create().as_mut(), config, limits, seed))
+        .collect();
+    BenchmarkResult::new(&results, fitness)
+}
+
+//没有指定种子时使用当前时间(毫秒)，调用者应该打印出来以便重现
+pub fn default_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+//在相同的种子上比较几个驾驶者，打印每个驾驶者的平均表现
+pub struct Comparison<'a> {
+    world: &'a WorldConfig,
+    limits: &'a EpisodeLimits,
+    episodes: usize,
+    seed: u64,
+    fitness: Box<dyn FitnessFunction>,
+}
+
+impl<'a> Comparison<'a> {
+    //fitness为None(未知的得分方式)时使用默认的加权得分
+    pub fn new(
+        world: &'a WorldConfig,
+        limits: &'a EpisodeLimits,
+        fitness: Option<Box<dyn FitnessFunction>>,
+        episodes: usize,
+        seed: u64,
+    ) -> Comparison<'a> {
+        let fitness = fitness.unwrap_or_else(|| Box::new(WeightedBlend::default()));
+        log!("比较 局数:{} 得分方式:{}", episodes, fitness.name());
+        Comparison {
+            world,
+            limits,
+            episodes,
+            seed,
+            fitness,
+        }
+    }
+
+    pub fn run<F>(&self, name: &str, create: F) -> BenchmarkResult
+    where
+        F: Fn() -> Box<dyn CarController> + Sync,
+    {
+        let result = benchmark(create, self.world, self.limits, self.episodes, self.seed, self.fitness.as_ref());
+        log!("{}: {}", name, result.summary());
+        result
+    }
+
+    //比较--load-brain加载的网络，没有指定时不比较
+    pub fn run_network<P: AsRef<Path>>(&self, path: Option<P>, sensors: &SensorConfig) {
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        match NetController::load(path.as_ref(), sensors.clone()) {
+            Ok(net) => {
+                self.run("network", || Box::new(net.clone()));
+            }
+            Err(err) => log!("加载网络失败:{:?} {:?}", path.as_ref(), err),
+        }
+    }
+}
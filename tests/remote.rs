@@ -0,0 +1,72 @@
+use serde_json::Value;
+use spaceout::ai::CarBrain;
+use spaceout::env::RewardConfig;
+use spaceout::remote::RemoteController;
+use spaceout::sensors::SensorConfig;
+use spaceout::simulation::{GameEvent, Simulation};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+//游戏一直没有结束时停止测试
+const MAX_FRAMES: usize = 100_000;
+
+//模拟的外部客户端：通过TCP读取每一帧的观测，一直向左行驶并开火，直到游戏结束
+#[test]
+fn mock_client_drives_car() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut frames = vec![];
+        for line in BufReader::new(stream).lines() {
+            let frame = serde_json::from_str::<Value>(&line.unwrap()).unwrap();
+            let game_over = frame["game_over"].as_bool().unwrap();
+            frames.push(frame);
+            //最后一帧不需要回复
+            if game_over {
+                break;
+            }
+            writeln!(writer, r#"{{"turn":"left","fire":true}}"#).unwrap();
+        }
+        frames
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let sensors = SensorConfig::default();
+    let remote = RemoteController::from_stream(stream, sensors.clone(), RewardConfig::default()).unwrap();
+    let mut simulation = Simulation::new(CarBrain::new());
+    simulation.set_driver(Some(Box::new(remote)));
+    simulation.new_game(false);
+    let mut fired = 0;
+    let mut hits = 0;
+    for _ in 0..MAX_FRAMES {
+        let result = simulation.step();
+        for event in result.events.iter() {
+            match event {
+                GameEvent::MissileFired { .. } => fired += 1,
+                GameEvent::CarHit { .. } => hits += 1,
+                _ => (),
+            }
+        }
+        if simulation.world().game_over() {
+            break;
+        }
+    }
+    assert!(simulation.world().game_over());
+    assert_eq!(simulation.driver_name(), "external");
+
+    let frames = client.join().unwrap();
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame["frame"].as_u64(), Some(i as u64));
+        assert_eq!(frame["observation"].as_array().unwrap().len(), sensors.num_inputs());
+        assert!(frame["reward"].is_f64());
+        //只有最后一帧是游戏结束
+        assert_eq!(frame["game_over"].as_bool(), Some(i == frames.len() - 1));
+    }
+    //每次汽车被击中都有一帧done，包括游戏结束的最后一帧
+    let done = frames.iter().filter(|frame| frame["done"].as_bool() == Some(true)).count();
+    assert_eq!(done, hits);
+    assert!(fired > 0);
+}